@@ -0,0 +1,257 @@
+//! Generates the board specific configuration from environment variables,
+//! so a board can pick its log console, FlexSPI clock and flash (size,
+//! sector map and connection) without editing the source.
+//!
+//! The log console:
+//!
+//! - `IMXRT_LOG_LPUART`: LPUART instance, 1 - 8 (default: 6)
+//! - `IMXRT_LOG_TX` / `IMXRT_LOG_RX`: pads, e.g. `GPIO_AD_B0_02` (defaults:
+//!   `GPIO_AD_B0_02` / `GPIO_AD_B0_03`, the TX and RX pins on the MicroMod ATP board)
+//! - `IMXRT_LOG_BAUD`: baud rate (default: 115200)
+//!
+//! Whether the pads actually belong to that LPUART is checked by the
+//! `imxrt-hal` iomuxc traits when the `log` feature is built.
+//!
+//! `IMXRT_LOG_LEVEL` (`off`, `error`, `warn`, `info`, `debug` or `trace`,
+//! default: `info`) sets the most verbose log level that is compiled in.
+//!
+//! `IMXRT_PROGRAM_CLOCK` (MHz, one of 30, 50, 60, 75, 80, 100, 120, 133 or
+//! 166, default: 30) sets the FlexSPI clock for erasing and programming.
+//! The algorithm drives the FlexSPI root clock and `ipcmdSerialClkFreq` from
+//! it, and falls back to 30 MHz if the start of the flash doesn't read back
+//! the same as at 30 MHz.
+//!
+//! `IMXRT_FLASH_SIZE` (bytes, or with a `K` / `M` suffix, default: `8M`) is
+//! the size of the flash. Above 16 MB, the algorithm switches the LUT the ROM
+//! probed to 4-byte addresses.
+//!
+//! `IMXRT_SECTOR_MAP` describes the erase sectors, as one of the layouts in
+//! `sector_map::SECTOR_MAPS` or as a list of `SIZE@OFFSET` regions, e.g.
+//! `4K@0,64K@0x10000`. Each region runs up to the next one, the last one to
+//! the end of the flash (default: `uniform-64k`).
+//!
+//! `IMXRT_FLASH_CONNECTION` is `single` (default), for one flash on port A1,
+//! or `parallel`, for two identical flashes on A1 and B1 that FlexSPI
+//! combines into one with twice the page, sector and total size. The size
+//! and sector map above always describe one flash.
+
+use std::env;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+// Shared with xtask, which checks the sectors an algorithm was built with.
+#[path = "src/sector_map.rs"]
+mod sector_map;
+
+use sector_map::{size_value, Offset, SECTOR_MAPS};
+
+const LPUART: &str = "IMXRT_LOG_LPUART";
+const TX: &str = "IMXRT_LOG_TX";
+const RX: &str = "IMXRT_LOG_RX";
+const BAUD: &str = "IMXRT_LOG_BAUD";
+const LEVEL: &str = "IMXRT_LOG_LEVEL";
+const PROGRAM_CLOCK: &str = "IMXRT_PROGRAM_CLOCK";
+const SECTOR_MAP: &str = "IMXRT_SECTOR_MAP";
+const FLASH_SIZE: &str = "IMXRT_FLASH_SIZE";
+const FLASH_CONNECTION: &str = "IMXRT_FLASH_CONNECTION";
+
+/// Size of the FlexSPI AHB window, 0x6000_0000 - 0x6F7F_FFFF.
+const FLEXSPI_WINDOW: u32 = 0x0F80_0000;
+
+const LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+
+/// FlexSPI clocks in MHz, in `serialClkFreq` order (code 1 is 30 MHz).
+const CLOCKS: &[u32] = &[30, 50, 60, 75, 80, 100, 120, 133, 166];
+
+/// Pad groups of the i.MX RT1060 and the number of pads in each.
+const PAD_GROUPS: &[(&str, u32)] = &[
+    ("EMC", 42),
+    ("AD_B0", 16),
+    ("AD_B1", 16),
+    ("B0", 16),
+    ("B1", 16),
+    ("SD_B0", 6),
+    ("SD_B1", 12),
+];
+
+struct Pad {
+    /// Type name, e.g. `gpio_ad_b0::GPIO_AD_B0_02`.
+    ty: String,
+    /// Field in `Pads`, e.g. `gpio_ad_b0.p02`.
+    field: String,
+}
+
+fn main() {
+    for var in [
+        LPUART,
+        TX,
+        RX,
+        BAUD,
+        LEVEL,
+        PROGRAM_CLOCK,
+        SECTOR_MAP,
+        FLASH_SIZE,
+        FLASH_CONNECTION,
+    ] {
+        println!("cargo:rerun-if-env-changed={var}");
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/sector_map.rs");
+
+    let instance: u8 = parse(LPUART, "6");
+    if !(1..=8).contains(&instance) {
+        panic!("{LPUART}={instance}: there are only LPUART1 - LPUART8");
+    }
+    let tx = pad(TX, "GPIO_AD_B0_02");
+    let rx = pad(RX, "GPIO_AD_B0_03");
+    let baud: u32 = parse(BAUD, "115200");
+
+    let mut out = String::new();
+    writeln!(out, "const LPUART_INSTANCE: u8 = {instance};").unwrap();
+    writeln!(out, "type TxPad = hal::iomuxc::pads::{};", tx.ty).unwrap();
+    writeln!(out, "type RxPad = hal::iomuxc::pads::{};", rx.ty).unwrap();
+    writeln!(out, "const BAUD: u32 = {baud};").unwrap();
+    writeln!(
+        out,
+        "fn take_pads(pads: hal::iomuxc::pads::Pads) -> (TxPad, RxPad) {{ (pads.{}, pads.{}) }}",
+        tx.field, rx.field
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "fn take_lpuart(instances: ral::Instances) -> ral::lpuart::Instance<LPUART_INSTANCE> {{ instances.LPUART{instance} }}"
+    )
+    .unwrap();
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("log_uart.rs"), out).unwrap();
+
+    let level = env::var(LEVEL).unwrap_or_else(|_| "info".into());
+    let max_level = LEVELS
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(level.trim()))
+        .unwrap_or_else(|| panic!("{LEVEL}={level}: expected one of {LEVELS:?}"));
+    std::fs::write(
+        out_dir.join("log_level.rs"),
+        format!("pub const MAX_LEVEL: u8 = {max_level};\n"),
+    )
+    .unwrap();
+
+    let mhz: u32 = parse(PROGRAM_CLOCK, "30");
+    let code = CLOCKS
+        .iter()
+        .position(|&clock| clock == mhz)
+        .unwrap_or_else(|| panic!("{PROGRAM_CLOCK}={mhz}: expected one of {CLOCKS:?} (MHz)"))
+        + 1;
+    std::fs::write(
+        out_dir.join("program_clock.rs"),
+        format!("pub const PROGRAM_CLOCK: u8 = {code};\n"),
+    )
+    .unwrap();
+
+    let value = env::var(FLASH_SIZE).unwrap_or_else(|_| "8M".into());
+    let flash_size = size_value(&value)
+        .unwrap_or_else(|| panic!("{FLASH_SIZE}={value}: expected a size like 0x800000 or 32M"));
+    let connection = env::var(FLASH_CONNECTION).unwrap_or_else(|_| "single".into());
+    let chips: u32 = match connection.trim() {
+        "single" => 1,
+        "parallel" => 2,
+        _ => panic!("{FLASH_CONNECTION}={connection}: expected `single` or `parallel`"),
+    };
+    if flash_size == 0 || flash_size > FLEXSPI_WINDOW / chips || flash_size & 0xFFF != 0 {
+        panic!(
+            "{FLASH_SIZE}={value}: expected a multiple of 4K, up to {:#x}",
+            FLEXSPI_WINDOW / chips
+        );
+    }
+    let mut out = String::new();
+    writeln!(out, "const CHIPS: u32 = {chips};").unwrap();
+    writeln!(out, "const FLASH_SIZE: u32 = {:#010x};", flash_size * chips).unwrap();
+    std::fs::write(out_dir.join("flash.rs"), out).unwrap();
+
+    std::fs::write(out_dir.join("sectors.rs"), sectors(&sector_map())).unwrap();
+}
+
+/// `IMXRT_SECTOR_MAP` as `(size, offset)` regions.
+fn sector_map() -> Vec<(u32, Offset)> {
+    let value = env::var(SECTOR_MAP).unwrap_or_else(|_| sector_map::DEFAULT.into());
+    sector_map::parse(&value).unwrap_or_else(|reason| {
+        let names: Vec<_> = SECTOR_MAPS.iter().map(|(name, _)| *name).collect();
+        panic!("{SECTOR_MAP}={value}: {reason} (expected one of {names:?} or SIZE@OFFSET,...)")
+    })
+}
+
+/// `SECTORS` for `geometry`, and `algorithm_with_sectors!`, which passes the
+/// same regions to `algorithm!`, scaled by `CHIPS`.
+fn sectors(regions: &[(u32, Offset)]) -> String {
+    let regions: Vec<_> = regions
+        .iter()
+        .map(|(size, offset)| {
+            let offset = match offset {
+                Offset::Start(0) => "0x0".to_string(),
+                Offset::Start(offset) => format!("{offset:#x} * CHIPS"),
+                Offset::End(offset) => format!("FLASH_SIZE - {offset:#x} * CHIPS"),
+            };
+            (format!("{size:#x} * CHIPS"), offset)
+        })
+        .collect();
+
+    let mut out = String::new();
+    writeln!(out, "const SECTORS: &[geometry::Sector] = &[").unwrap();
+    for (size, offset) in &regions {
+        writeln!(
+            out,
+            "    geometry::Sector {{ size: {size}, address: {offset} }},"
+        )
+        .unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    writeln!(out, "macro_rules! algorithm_with_sectors {{").unwrap();
+    writeln!(out, "    ($name:ident, {{ $($field:tt)* }}) => {{").unwrap();
+    writeln!(out, "        algorithm!($name, {{").unwrap();
+    writeln!(out, "            $($field)*").unwrap();
+    let sectors: Vec<_> = regions
+        .iter()
+        .map(|(size, offset)| format!("{{ size: {size}, address: {offset}, }}"))
+        .collect();
+    writeln!(out, "            sectors: [{}]", sectors.join(", ")).unwrap();
+    writeln!(out, "        }});").unwrap();
+    writeln!(out, "    }};").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn parse<T: std::str::FromStr>(var: &str, default: &str) -> T {
+    let value = env::var(var).unwrap_or_else(|_| default.into());
+    value
+        .trim()
+        .parse()
+        .unwrap_or_else(|_| panic!("{var}={value}: not a valid number"))
+}
+
+fn pad(var: &str, default: &str) -> Pad {
+    let value = env::var(var).unwrap_or_else(|_| default.into());
+    let name = value.trim().to_ascii_uppercase();
+
+    let invalid = || -> ! { panic!("{var}={value}: expected a pad name like GPIO_AD_B0_02") };
+
+    let rest = name.strip_prefix("GPIO_").unwrap_or_else(|| invalid());
+    let (group, index) = rest.rsplit_once('_').unwrap_or_else(|| invalid());
+    let index: u32 = index.parse().unwrap_or_else(|_| invalid());
+
+    let count = PAD_GROUPS
+        .iter()
+        .find(|(name, _)| *name == group)
+        .map(|(_, count)| *count)
+        .unwrap_or_else(|| invalid());
+    if index >= count {
+        panic!("{var}={value}: GPIO_{group} only has {count} pads");
+    }
+
+    let module = format!("gpio_{}", group.to_ascii_lowercase());
+    Pad {
+        ty: format!("{module}::GPIO_{group}_{index:02}"),
+        field: format!("{module}.p{index:02}"),
+    }
+}
@@ -0,0 +1,279 @@
+//! The boot ROM's view of the flash.
+//!
+//! Images that boot from FlexSPI NOR carry an FCB at offset 0, and that's
+//! what the boot ROM configures FlexSPI from. With the `fcb` feature, the
+//! algorithm uses that same block as `NOR_CONFIG` and only probes the flash
+//! with `flexspi_nor_get_config` if there is no valid FCB.
+//!
+//! A bad FCB or IVT leaves a board that doesn't boot. With the `boot-check`
+//! feature, pages programmed into the boot header (the first
+//! [`HEADER_SIZE`] bytes: FCB at 0x0, IVT at 0x1000, boot data and usually
+//! the DCD) are held back until the header is complete, checked, and only
+//! then programmed. The host can skip the check by writing a non-zero value
+//! to `BOOT_CHECK_OVERRIDE` before programming.
+
+use crate::fcb::{self, Fcb};
+#[cfg(feature = "fcb")]
+use crate::rom_api::flexspi_nor_config_t;
+
+/// FlexSPI MCR0 register, MDIS (module disable) is bit 1.
+#[cfg(feature = "fcb")]
+const FLEXSPI_MCR0: *const u32 = 0x402A_8000 as *const u32;
+#[cfg(feature = "fcb")]
+const FLEXSPI_MCR0_MDIS: u32 = 1 << 1;
+
+/// Read the FCB at the start of the flash, if FlexSPI is up (the boot ROM
+/// or an earlier Init configured it) and the block is valid.
+#[cfg(feature = "fcb")]
+pub unsafe fn load_fcb() -> Option<flexspi_nor_config_t> {
+    if core::ptr::read_volatile(FLEXSPI_MCR0) & FLEXSPI_MCR0_MDIS != 0 {
+        crate::log_debug!("fcb: FlexSPI is disabled");
+        return None;
+    }
+
+    crate::rom_api::flexspi_clear_cache(crate::FLEXSPI_INSTANCE);
+    let bytes =
+        core::ptr::read_volatile(crate::MEMORY_MAP_FLEXSPI_START_ADDRESS as *const [u8; fcb::SIZE]);
+
+    match Fcb::parse(&bytes) {
+        Ok(_) => Some(core::ptr::read_unaligned(
+            bytes.as_ptr() as *const flexspi_nor_config_t
+        )),
+        Err(_err) => {
+            crate::log_debug!(
+                "fcb: invalid {} at offset {}: {}",
+                _err.field.name(),
+                _err.field.offset(),
+                _err.value
+            );
+            None
+        }
+    }
+}
+
+/// Bytes at the start of the flash that are held back and checked.
+#[cfg(feature = "boot-check")]
+pub const HEADER_SIZE: usize = 0x2000;
+
+/// Offset of the IVT for FlexSPI NOR boot.
+#[cfg(feature = "boot-check")]
+const IVT_OFFSET: usize = 0x1000;
+#[cfg(feature = "boot-check")]
+const IVT_SIZE: usize = 0x20;
+#[cfg(feature = "boot-check")]
+const IVT_TAG: u8 = 0xD1;
+#[cfg(feature = "boot-check")]
+const DCD_TAG: u8 = 0xD2;
+/// Largest DCD the ROM accepts.
+#[cfg(feature = "boot-check")]
+const DCD_MAX_SIZE: u32 = 1768;
+
+/// Non-zero: program the boot header without checking it.
+#[cfg(feature = "boot-check")]
+#[no_mangle]
+#[used]
+pub static mut BOOT_CHECK_OVERRIDE: u32 = 0;
+
+#[cfg(feature = "boot-check")]
+static mut CHECKER: Checker = Checker {
+    header: [0; HEADER_SIZE],
+    held: 0,
+    checked: false,
+};
+
+#[cfg(feature = "boot-check")]
+pub unsafe fn checker() -> &'static mut Checker {
+    &mut CHECKER
+}
+
+/// Holds the boot header pages until they can be checked.
+#[cfg(feature = "boot-check")]
+pub struct Checker {
+    header: [u8; HEADER_SIZE],
+    /// Bit n: page n of the header was programmed (and is held).
+    held: u32,
+    /// The header was checked and programmed. Header pages after that, past
+    /// the end of an image that ends inside the header, aren't held.
+    checked: bool,
+}
+
+#[cfg(feature = "boot-check")]
+const HEADER_PAGES: usize = HEADER_SIZE / crate::PAGE_SIZE as usize;
+#[cfg(feature = "boot-check")]
+const _: () = assert!(HEADER_PAGES <= 32);
+
+#[cfg(feature = "boot-check")]
+impl Checker {
+    /// Whether a page at `flash_addr` is held back.
+    fn holds(&self, flash_addr: u32) -> bool {
+        !self.checked && (flash_addr as usize) < HEADER_SIZE && unsafe { BOOT_CHECK_OVERRIDE } == 0
+    }
+
+    /// Program `page` at `flash_addr` with `write`, unless it's part of the
+    /// boot header. The first page past the header checks and programs the
+    /// held header first.
+    ///
+    /// The header is also checked as its pages come in, so it's rejected as
+    /// soon as it's inconsistent, and programmed by the page that completes
+    /// an image that ends inside it.
+    pub fn program(&mut self, flash_addr: u32, page: &[u8], write: fn(u32, &[u8]) -> u32) -> u32 {
+        if self.holds(flash_addr) {
+            if self.held == 0 {
+                // Whatever isn't programmed stays erased.
+                self.header = [0xFF; HEADER_SIZE];
+            }
+            let start = flash_addr as usize;
+            let end = (start + page.len()).min(HEADER_SIZE);
+            self.header[start..end].copy_from_slice(&page[..end - start]);
+
+            let page_size = crate::PAGE_SIZE as usize;
+            for index in start / page_size..(end + page_size - 1) / page_size {
+                self.held |= 1 << index;
+            }
+            return self.check_held(write);
+        }
+
+        let status = self.finish(write);
+        if status != 0 {
+            return status;
+        }
+        write(flash_addr, page)
+    }
+
+    /// Check the header as far as it's held without gaps.
+    fn check_held(&mut self, write: fn(u32, &[u8]) -> u32) -> u32 {
+        let available = self.held.trailing_ones() as usize * crate::PAGE_SIZE as usize;
+        match check(&self.header, available) {
+            Ok(Some(size)) if size as usize <= available => self.finish(write),
+            Ok(_) => 0,
+            Err(code) => {
+                self.held = 0;
+                reject(code)
+            }
+        }
+    }
+
+    /// Check the held header, and program it if it's valid. Called by the
+    /// first page past the header, and at the end of the image.
+    pub fn finish(&mut self, write: fn(u32, &[u8]) -> u32) -> u32 {
+        if self.held == 0 {
+            return 0;
+        }
+        let held = core::mem::take(&mut self.held);
+
+        if let Err(code) = check(&self.header, HEADER_SIZE) {
+            return reject(code);
+        }
+        self.checked = true;
+
+        let page_size = crate::PAGE_SIZE as usize;
+        for index in 0..HEADER_PAGES {
+            if held & (1 << index) != 0 {
+                let offset = index * page_size;
+                let status = write(offset as u32, &self.header[offset..offset + page_size]);
+                if status != 0 {
+                    return status;
+                }
+            }
+        }
+        0
+    }
+
+    /// Start over for the next session.
+    pub fn reset(&mut self) {
+        self.held = 0;
+        self.checked = false;
+    }
+}
+
+#[cfg(feature = "boot-check")]
+fn reject(code: u32) -> u32 {
+    crate::log_error!("Boot header rejected: {}", crate::error::message(code));
+    code
+}
+
+#[cfg(feature = "boot-check")]
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// Check the FCB, IVT, boot data and DCD in the first bytes of the flash.
+/// Returns the image size from the boot data, or an `error::BOOT_*` code for
+/// the first inconsistency.
+///
+/// Only the first `available` bytes of `header` are there yet; `Ok(None)` if
+/// that's not enough to tell. Everything the IVT points to has to lie inside
+/// `header`.
+#[cfg(feature = "boot-check")]
+pub fn check(header: &[u8], available: usize) -> Result<Option<u32>, u32> {
+    use crate::error::*;
+
+    let missing = |end: usize| end > available;
+
+    if missing(fcb::SIZE) {
+        return Ok(None);
+    }
+    Fcb::parse(&header[..fcb::SIZE]).map_err(|_| BOOT_FCB_INVALID)?;
+
+    if missing(IVT_OFFSET + IVT_SIZE) {
+        return Ok(None);
+    }
+    let ivt = &header[IVT_OFFSET..IVT_OFFSET + IVT_SIZE];
+    let length = u16::from_be_bytes([ivt[1], ivt[2]]) as usize;
+    if ivt[0] != IVT_TAG || length != IVT_SIZE || !matches!(ivt[3], 0x40 | 0x41) {
+        return Err(BOOT_IVT_INVALID);
+    }
+    let entry = u32_at(ivt, 4);
+    let dcd = u32_at(ivt, 12);
+    let boot_data = u32_at(ivt, 16);
+    let self_ptr = u32_at(ivt, 20);
+
+    // The IVT's own address tells where the image is (going to be) loaded.
+    let image_start = self_ptr
+        .checked_sub(IVT_OFFSET as u32)
+        .ok_or(BOOT_IVT_SELF_INVALID)?;
+    let ivt_end = self_ptr
+        .checked_add(IVT_SIZE as u32)
+        .ok_or(BOOT_IVT_SELF_INVALID)?;
+    let offset_of = |address: u32, len: usize| -> Option<usize> {
+        let offset = address.checked_sub(image_start)? as usize;
+        (offset.checked_add(len)? <= header.len()).then_some(offset)
+    };
+
+    let boot_data = offset_of(boot_data, 12).ok_or(BOOT_DATA_INVALID)?;
+    if missing(boot_data + 12) {
+        return Ok(None);
+    }
+    let start = u32_at(header, boot_data);
+    let size = u32_at(header, boot_data + 4);
+    if start != image_start || (size as usize) < IVT_OFFSET + IVT_SIZE {
+        return Err(BOOT_DATA_INVALID);
+    }
+    let end = start as u64 + size as u64;
+
+    if !(start as u64..end).contains(&(entry as u64)) || entry < ivt_end {
+        return Err(BOOT_ENTRY_INVALID);
+    }
+
+    if dcd != 0 {
+        let dcd = offset_of(dcd, 4).ok_or(BOOT_DCD_INVALID)?;
+        if missing(dcd + 4) {
+            return Ok(None);
+        }
+        let length = u16::from_be_bytes([header[dcd + 1], header[dcd + 2]]) as u32;
+        if header[dcd] != DCD_TAG
+            || !matches!(header[dcd + 3], 0x40 | 0x41)
+            || !(4..=DCD_MAX_SIZE).contains(&length)
+            || dcd + length as usize > header.len()
+        {
+            return Err(BOOT_DCD_INVALID);
+        }
+    }
+
+    Ok(Some(size))
+}
@@ -0,0 +1,49 @@
+//! Checksums over a range of flash.
+//!
+//! Reading an 8 MB image back over SWD takes far longer than hashing it on the
+//! target. The `Crc32` (and with the `sha256` feature, `Sha256`) entry points
+//! hash a flash range through the AHB window and leave the digest in
+//! `CHECKSUM_RESULT`, so the host only has to read back a few words.
+//!
+//! Both entry points expect `Init` to have been called, so that FlexSPI is
+//! configured for memory-mapped reads.
+
+use crate::hash::Crc32;
+#[cfg(feature = "sha256")]
+use crate::hash::Sha256;
+
+/// Digest of the last successful checksum call. CRC32 only uses the first
+/// word, SHA-256 uses all eight (big endian byte order, as usual).
+#[no_mangle]
+#[used]
+pub static mut CHECKSUM_RESULT: [u32; 8] = [0; 8];
+
+/// Compute the CRC32 (IEEE 802.3) of `size` bytes of flash starting at
+/// `address`, and store it in `CHECKSUM_RESULT[0]`.
+#[no_mangle]
+#[link_section = ".entry"]
+pub unsafe extern "C" fn Crc32(address: u32, size: u32) -> u32 {
+    let mut crc = Crc32::new();
+    if let Err(code) = crate::die::for_each_slice(address, size, |data| crc.update(data)) {
+        return code;
+    }
+
+    CHECKSUM_RESULT = [0; 8];
+    CHECKSUM_RESULT[0] = crc.finish();
+    0
+}
+
+/// Compute the SHA-256 of `size` bytes of flash starting at `address`, and
+/// store it in `CHECKSUM_RESULT`.
+#[cfg(feature = "sha256")]
+#[no_mangle]
+#[link_section = ".entry"]
+pub unsafe extern "C" fn Sha256(address: u32, size: u32) -> u32 {
+    let mut sha = Sha256::new();
+    if let Err(code) = crate::die::for_each_slice(address, size, |data| sha.update(data)) {
+        return code;
+    }
+
+    CHECKSUM_RESULT = sha.finish();
+    0
+}
@@ -6,7 +6,10 @@ pub unsafe fn init() {
     if !INIT {
         disable_watchdog();
         disable_systick();
+        enable_cycle_counter();
         clock_setup();
+        #[cfg(feature = "log-itm")]
+        trace_setup();
         INIT = true;
     }
 }
@@ -20,6 +23,34 @@ unsafe fn disable_systick() {
     periphs.SYST.disable_counter();
 }
 
+unsafe fn enable_cycle_counter() {
+    // get ARM Cortex peripherals access (without taking/stealing them)
+    let mut periphs: cortex_m::Peripherals = core::mem::transmute(());
+    periphs.DCB.enable_trace();
+    // The Cortex-M7 DWT ignores writes until it's unlocked.
+    periphs.DWT.lar.write(0xC5AC_CE55);
+    periphs.DWT.enable_cycle_counter();
+}
+
+/// Drop whatever the D-cache holds for `len` bytes at `address`, so the next
+/// read goes to the memory. Only for memory the core doesn't write, like the
+/// FlexSPI window: dirty lines are discarded, not written back.
+pub unsafe fn invalidate_dcache(address: u32, len: u32) {
+    // get ARM Cortex peripherals access (without taking/stealing them)
+    let mut periphs: cortex_m::Peripherals = core::mem::transmute(());
+    if cortex_m::peripheral::SCB::dcache_enabled() {
+        periphs
+            .SCB
+            .invalidate_dcache_by_address(address as usize, len as usize);
+    }
+}
+
+/// Core clock cycles since `init`, wrapping around.
+#[allow(dead_code)]
+pub fn cycles() -> u32 {
+    cortex_m::peripheral::DWT::cycle_count()
+}
+
 unsafe fn disable_watchdog() {
     // Disable Power Down Counter of WDOG1.
     ral::modify_reg!(ral::wdog, WDOG1, WMCR, PDE: PDE_0);
@@ -98,6 +129,20 @@ fn rom_ocotp_boot_freq_value() -> u32 {
     }
 }
 
+/// The core clock `clock_setup` runs the chip at.
+pub fn core_clock() -> u32 {
+    const FREQ_396MHZ: u32 = 396_000_000;
+    const FREQ_528MHZ: u32 = 528_000_000;
+
+    let clock_divider = 1 << rom_ocotp_lpb_boot_value();
+    let clock = if rom_ocotp_boot_freq_value() == 0 {
+        FREQ_396MHZ
+    } else {
+        FREQ_528MHZ
+    };
+    clock / clock_divider
+}
+
 unsafe fn clock_setup() {
     let fuse_div = rom_ocotp_lpb_boot_value();
     let clock_freq = rom_ocotp_boot_freq_value();
@@ -204,16 +249,10 @@ unsafe fn clock_setup() {
     // Set up CPU_PODF
     ral::write_reg!(ral::ccm, CCM, CACRR, ARM_PODF: ARM_PODF_1);
 
-    const FREQ_396MHZ: u32 = 396_000_000;
-    const FREQ_528MHZ: u32 = 528_000_000;
     const MAX_AHB_CLOCK: u32 = 144_000_000;
 
     // Calculate the Final System Core Clock, it will be used to calculate the AHB / ARM Core divider later.
-    let system_core_clock = if clock_freq == 0 {
-        FREQ_396MHZ
-    } else {
-        FREQ_528MHZ
-    } / clock_divider;
+    let system_core_clock = core_clock();
 
     // Calculate the AHB clock divider
     let mut ahb_divider = 1;
@@ -247,14 +286,14 @@ unsafe fn clock_setup() {
         LPSPI_PODF: LPSPI_PODF_5,
     );
 
-    // FLEXSPI clock configuration, safe frequency: 30MHz
     ral::modify_reg!(ral::ccm, CCM, CSCMR1,
-        FLEXSPI_CLK_SEL: FLEXSPI_CLK_SEL_3,
-        FLEXSPI_PODF: FLEXSPI_PODF_7,
         PERCLK_CLK_SEL: PERCLK_CLK_SEL_0,
         PERCLK_PODF: DIVIDE_2,
     );
 
+    // FLEXSPI clock configuration, safe frequency: 30MHz
+    set_flexspi_clock(SAFE_CLOCK);
+
     // NOTE: SEMC clock configuration needs handshake, so it will be handled by SEMC driver itself
     // uSDHC1&2 clock configuration
     // SEL: PULLL2 PFD0; DIV: 1 (PFD/2, freq=200MHz)
@@ -287,3 +326,83 @@ unsafe fn clock_setup() {
         BYPASS: 0,
     );
 }
+
+// PROGRAM_CLOCK, generated by build.rs from IMXRT_PROGRAM_CLOCK.
+include!(concat!(env!("OUT_DIR"), "/program_clock.rs"));
+
+/// `serialClkFreq` code of the 30 MHz clock every flash keeps up with.
+pub const SAFE_CLOCK: u8 = 1;
+
+/// FlexSPI clocks in MHz, in `serialClkFreq` order (code 1 is 30 MHz).
+const CLOCKS_MHZ: [u32; 9] = [30, 50, 60, 75, 80, 100, 120, 133, 166];
+
+/// The `serialClkFreq` code of the fastest clock that doesn't exceed `hz`,
+/// 30 MHz at the least.
+pub fn clock_code(hz: u32) -> u8 {
+    CLOCKS_MHZ
+        .iter()
+        .rposition(|&mhz| mhz * 1_000_000 <= hz)
+        .map_or(SAFE_CLOCK, |index| index as u8 + 1)
+}
+
+/// Set the FlexSPI root clock to a `serialClkFreq` code: 30 MHz at init,
+/// the programming clock from `init_flash`. Unknown codes select 30 MHz.
+///
+/// The root is PLL3 PFD0 (480MHz * 18 / frac) / podf, rounded down to the
+/// closest frequency we can get.
+pub unsafe fn set_flexspi_clock(freq: u8) {
+    let (frac, podf) = match freq {
+        2 => (29, 6), // 49.7MHz
+        3 => (24, 6), // 60MHz
+        4 => (29, 4), // 74.5MHz
+        5 => (18, 6), // 80MHz
+        6 => (29, 3), // 99.3MHz
+        7 => (24, 3), // 120MHz
+        8 => (13, 5), // 132.9MHz
+        9 => (13, 4), // 166.2MHz
+        _ => (35, 8), // 30.9MHz
+    };
+
+    // Gate the FlexSPI clock while switching it.
+    ral::modify_reg!(ral::ccm, CCM, CCGR6, CG5: 0);
+    ral::modify_reg!(ral::ccm_analog, CCM_ANALOG, PFD_480, PFD0_FRAC: frac);
+    ral::modify_reg!(ral::ccm, CCM, CSCMR1,
+        FLEXSPI_CLK_SEL: FLEXSPI_CLK_SEL_3,
+        FLEXSPI_PODF: podf - 1,
+    );
+    ral::modify_reg!(ral::ccm, CCM, CCGR6, CG5: 0b11);
+}
+
+/// Trace clock: PLL2 PFD0 (396MHz) / 3
+#[cfg(feature = "log-itm")]
+const TRACE_CLOCK: u32 = 132_000_000;
+
+/// SWO baud rate, the trace clock has to be a multiple of it.
+#[cfg(feature = "log-itm")]
+pub const SWO_BAUD: u32 = 6_000_000;
+
+/// Route ITM stimulus port 0 to the SWO pin.
+#[cfg(feature = "log-itm")]
+unsafe fn trace_setup() {
+    // Trace clock root, see TRACE_CLOCK
+    ral::modify_reg!(ral::ccm, CCM, CBCMR, TRACE_CLK_SEL: 2);
+    ral::modify_reg!(ral::ccm, CCM, CSCDR1, TRACE_PODF: 2);
+    ral::modify_reg!(ral::ccm, CCM, CCGR0, CG11: 0b11);
+
+    // GPIO_AD_B0_10, ALT9: ARM_TRACE_SWO (out of reset it's JTAG_TDO)
+    ral::write_reg!(ral::iomuxc, IOMUXC, SW_MUX_CTL_PAD_GPIO_AD_B0_10, MUX_MODE: 9);
+
+    // get ARM Cortex peripherals access (without taking/stealing them)
+    let mut periphs: cortex_m::Peripherals = core::mem::transmute(());
+    periphs.DCB.enable_trace();
+
+    // TPIU: asynchronous SWO, NRZ (UART) encoding, formatter bypassed
+    periphs.TPIU.acpr.write(TRACE_CLOCK / SWO_BAUD - 1);
+    periphs.TPIU.sppr.write(2);
+    periphs.TPIU.ffcr.write(0x100);
+
+    // ITM: unlock, enable with trace bus ID 1 and enable stimulus port 0
+    periphs.ITM.lar.write(0xC5AC_CE55);
+    periphs.ITM.tcr.write((1 << 16) | 1);
+    periphs.ITM.ter[0].write(1);
+}
@@ -0,0 +1,118 @@
+//! Flash commands the ROM's NOR driver doesn't issue.
+//!
+//! Each command is loaded into a LUT sequence the driver doesn't use and run
+//! with `flexspi_command_xfer`. Commands have no address, and a value of up
+//! to four bytes per flash, on a single pad; [`send`] runs any
+//! sequence without data.
+//!
+//! In parallel mode, both flashes get the same command, and their data bytes
+//! are interleaved (A first). [`read`] and [`write`] take care of that and
+//! deal in one value per flash, indexed by chip.
+
+use crate::fcb::{self, Instr, Opcode, Pads, Sequence};
+use crate::rom_api::{self, flexspi_xfer_t};
+
+/// LUT sequence the commands are loaded into.
+const SEQ_SCRATCH: u32 = 12;
+
+/// Values of up to [`MAX_LEN`] bytes, one per flash.
+pub type Values = [u32; 2];
+
+/// Longest value, per flash.
+pub const MAX_LEN: u32 = 4;
+
+/// Send `cmd`, with no data.
+pub unsafe fn command(cmd: u8) -> u32 {
+    send(&fcb::sequence(&[Instr::new(
+        Opcode::CmdSdr,
+        Pads::Single,
+        cmd,
+    )]))
+}
+
+/// Run `seq`, which doesn't transfer any data.
+pub unsafe fn send(seq: &Sequence) -> u32 {
+    run(
+        seq,
+        rom_api::_FlexSPIOperationType_kFlexSpiOperation_Command,
+        core::ptr::null_mut(),
+        0,
+    )
+}
+
+/// Send `cmd` and read `len` bytes from each flash.
+pub unsafe fn read(cmd: u8, len: u32) -> Result<Values, u32> {
+    let seq = fcb::sequence(&[
+        Instr::new(Opcode::CmdSdr, Pads::Single, cmd),
+        Instr::new(Opcode::ReadSdr, Pads::Single, len as u8),
+    ]);
+    let mut raw: u64 = 0;
+    let status = run(
+        &seq,
+        rom_api::_FlexSPIOperationType_kFlexSpiOperation_Read,
+        &mut raw as *mut u64 as *mut u32,
+        len * crate::CHIPS,
+    );
+    if status != 0 {
+        return Err(status);
+    }
+
+    let mut values = [0; 2];
+    for byte in 0..len {
+        for (chip, value) in values.iter_mut().take(crate::CHIPS as usize).enumerate() {
+            let shift = 8 * (byte * crate::CHIPS + chip as u32);
+            *value |= ((raw >> shift & 0xFF) as u32) << (8 * byte);
+        }
+    }
+    Ok(values)
+}
+
+/// Send `cmd` followed by `len` bytes of each flash's value.
+pub unsafe fn write(cmd: u8, values: Values, len: u32) -> u32 {
+    let seq = fcb::sequence(&[
+        Instr::new(Opcode::CmdSdr, Pads::Single, cmd),
+        Instr::new(Opcode::WriteSdr, Pads::Single, len as u8),
+    ]);
+
+    let mut raw: u64 = 0;
+    for byte in 0..len {
+        for (chip, value) in values.iter().take(crate::CHIPS as usize).enumerate() {
+            let shift = 8 * (byte * crate::CHIPS + chip as u32);
+            raw |= ((value >> (8 * byte) & 0xFF) as u64) << shift;
+        }
+    }
+    run(
+        &seq,
+        rom_api::_FlexSPIOperationType_kFlexSpiOperation_Write,
+        &mut raw as *mut u64 as *mut u32,
+        len * crate::CHIPS,
+    )
+}
+
+unsafe fn run(
+    seq: &Sequence,
+    operation: rom_api::flexspi_operation_t,
+    data: *mut u32,
+    size: u32,
+) -> u32 {
+    let words = fcb::lut_words(seq);
+    let status =
+        rom_api::flexspi_update_lut(crate::FLEXSPI_INSTANCE, SEQ_SCRATCH, words.as_ptr(), 1);
+    if status != 0 {
+        return status;
+    }
+
+    let read = operation == rom_api::_FlexSPIOperationType_kFlexSpiOperation_Read;
+    let mut xfer = flexspi_xfer_t {
+        operation,
+        baseAddress: 0,
+        seqId: SEQ_SCRATCH,
+        seqNum: 1,
+        isParallelModeEnable: crate::CHIPS == 2,
+        txBuffer: if read { core::ptr::null_mut() } else { data },
+        txSize: if read { 0 } else { size },
+        rxBuffer: if read { data } else { core::ptr::null_mut() },
+        rxSize: if read { size } else { 0 },
+    };
+    rom_api::flexspi_command_xfer(crate::FLEXSPI_INSTANCE, &mut xfer)
+}
@@ -0,0 +1,187 @@
+//! Stacked-die NOR flashes.
+//!
+//! Parts like the W25M512JV stack two identical dies behind one chip select.
+//! Only one die is active at a time, chosen with Software Die Select (C2h):
+//! commands and memory-mapped reads go to the active die, which ignores the
+//! address bits above its own size. With the `stacked-die` feature, the
+//! algorithm reads the JEDEC ID at init, and if it's one of [`PARTS`], selects
+//! the die an address falls on before erasing, programming or hashing there.
+//!
+//! The debugger's verify reads the memory-mapped window, which only sees
+//! the selected die. The feature also enables the algorithm's `Verify`
+//! entry point, which compares the flash one die at a time, see
+//! [`for_each_slice`].
+//!
+//! Without the feature, [`select`] does nothing.
+
+#[cfg(feature = "stacked-die")]
+use crate::command;
+#[cfg(feature = "stacked-die")]
+use crate::rom_api::{self, flexspi_nor_config_t};
+
+/// Stacked-die parts: JEDEC ID (manufacturer, memory type, capacity),
+/// number of dies and size of each die.
+#[cfg(feature = "stacked-die")]
+const PARTS: &[([u8; 3], u32, u32)] = &[
+    // W25M512JV: 2x W25Q256JV
+    ([0xEF, 0x71, 0x19], 2, 32 * 1024 * 1024),
+];
+
+#[cfg(feature = "stacked-die")]
+const READ_ID: u8 = 0x9F;
+#[cfg(feature = "stacked-die")]
+const DIE_SELECT: u8 = 0xC2;
+
+#[cfg(feature = "stacked-die")]
+struct Dies {
+    count: u32,
+    size: u32,
+    selected: u32,
+}
+
+#[cfg(feature = "stacked-die")]
+static mut DIES: Dies = Dies {
+    count: 1,
+    size: 0,
+    selected: 0,
+};
+
+/// Read the JEDEC ID and, for a stacked-die part, make the ROM driver map
+/// all of its dies. Called with FlexSPI up, before the ROM driver is
+/// initialized from `config`.
+#[cfg(feature = "stacked-die")]
+pub unsafe fn detect(config: &mut flexspi_nor_config_t) -> u32 {
+    DIES = Dies {
+        count: 1,
+        size: 0,
+        selected: 0,
+    };
+    // Die select doesn't go with parallel mode.
+    if crate::CHIPS != 1 {
+        return 0;
+    }
+
+    let id = match command::read(READ_ID, 3) {
+        Ok(values) => values[0],
+        Err(status) => return status,
+    };
+    let [manufacturer, memory_type, capacity, _] = id.to_le_bytes();
+    crate::log_info!("JEDEC ID: {} {} {}", manufacturer, memory_type, capacity);
+    let (count, size) = match PARTS
+        .iter()
+        .find(|(part, _, _)| *part == [manufacturer, memory_type, capacity])
+    {
+        Some(&(_, count, size)) => (count, size),
+        None => return 0,
+    };
+
+    if crate::FLASH_SIZE <= size {
+        crate::log_info!("die: only using the first die");
+        return 0;
+    }
+    if crate::FLASH_SIZE != count * size {
+        crate::log_error!(
+            "die: {} dies of {} bytes, but FLASH_SIZE is {}",
+            count,
+            size,
+            crate::FLASH_SIZE
+        );
+        return crate::error::STACKED_DIE_SIZE_MISMATCH;
+    }
+
+    crate::log_info!("die: {} dies of {} bytes", count, size);
+    // The ROM probes one die, map all of them.
+    config.memConfig.sflashA1Size = crate::FLASH_SIZE;
+    DIES = Dies {
+        count,
+        size,
+        selected: u32::MAX,
+    };
+    select(0)
+}
+
+/// Make the die that holds `flash_addr` (an offset into the flash) the
+/// active one. The flash must not be busy.
+#[cfg(feature = "stacked-die")]
+pub unsafe fn select(flash_addr: u32) -> u32 {
+    if DIES.count <= 1 {
+        return 0;
+    }
+
+    let die = flash_addr / DIES.size;
+    if die == DIES.selected {
+        return 0;
+    }
+
+    let status = command::write(DIE_SELECT, [die, 0], 1);
+    if status != 0 {
+        DIES.selected = u32::MAX;
+        return status;
+    }
+
+    // The AHB buffers may hold data of the previous die.
+    rom_api::flexspi_clear_cache(crate::FLEXSPI_INSTANCE);
+    DIES.selected = die;
+    0
+}
+
+#[cfg(not(feature = "stacked-die"))]
+pub unsafe fn select(_flash_addr: u32) -> u32 {
+    0
+}
+
+/// End of the die that holds `flash_addr`, as an offset into the flash.
+#[cfg(feature = "stacked-die")]
+pub unsafe fn end_of(flash_addr: u32) -> u32 {
+    if DIES.count <= 1 {
+        return crate::FLASH_SIZE;
+    }
+    (flash_addr / DIES.size + 1) * DIES.size
+}
+
+#[cfg(not(feature = "stacked-die"))]
+pub unsafe fn end_of(_flash_addr: u32) -> u32 {
+    crate::FLASH_SIZE
+}
+
+/// Number of dies found by [`detect`].
+#[cfg(feature = "stacked-die")]
+pub unsafe fn count() -> u32 {
+    DIES.count
+}
+
+#[cfg(not(feature = "stacked-die"))]
+pub unsafe fn count() -> u32 {
+    1
+}
+
+/// Hand a range of the memory mapped flash to `f`, after making sure the
+/// FlexSPI AHB buffers and the D-cache don't hold stale data. On a
+/// stacked-die part, that's one slice per die, each read with its die
+/// selected; die 0 is selected again afterwards.
+pub unsafe fn for_each_slice(address: u32, size: u32, mut f: impl FnMut(&[u8])) -> Result<(), u32> {
+    let mut flash_addr = crate::GEOMETRY.range(address, size)?;
+    let end = flash_addr + size;
+
+    while flash_addr < end {
+        let status = select(flash_addr);
+        if status != 0 {
+            return Err(status);
+        }
+        let len = end_of(flash_addr).min(end) - flash_addr;
+        let address = crate::MEMORY_MAP_FLEXSPI_START_ADDRESS + flash_addr;
+        // The cache may hold the other die at the same addresses.
+        crate::invalidate(address, len);
+        f(core::slice::from_raw_parts(
+            address as *const u8,
+            len as usize,
+        ));
+        flash_addr += len;
+    }
+
+    let status = select(0);
+    if status != 0 {
+        return Err(status);
+    }
+    Ok(())
+}
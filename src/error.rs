@@ -0,0 +1,150 @@
+//! Error codes returned by the flash algorithm.
+//!
+//! Every failing entry point returns a non-zero `u32`. The codes are split
+//! into non-overlapping ranges, so the host can tell where a failure came
+//! from:
+//!
+//! | Range           | Source                                                  |
+//! |-----------------|---------------------------------------------------------|
+//! | 1 – 29999       | ROM `spi_status_t`, passed through unchanged            |
+//! | 30000 – 30999   | Geometry: addresses, lengths and alignment              |
+//! | 31000 – 31999   | Decompressor (`miniz` transfer encoding)                |
+//! | 32000 – 32999   | Configuration: FlexSPI / NOR setup done by the algorithm |
+//! | 33000 – 33999   | Boot header check (`boot-check` feature)                |
+//!
+//! The ROM builds its status codes as `group * 100 + code`, and its largest
+//! group (FlexSPI NOR, 201) ends well below 30000.
+//!
+//! This module doesn't depend on anything else in the crate, so host tooling
+//! can pull it in with `#[path = "…/src/error.rs"] mod error;` and use
+//! [`message`] to turn a code back into text.
+
+#![allow(dead_code)]
+
+/// Highest code that belongs to the ROM.
+pub const ROM_LAST: u32 = 29999;
+
+pub const GEOMETRY_BASE: u32 = 30000;
+/// The address isn't inside the flash's part of the FlexSPI window.
+pub const ADDRESS_OUT_OF_RANGE: u32 = GEOMETRY_BASE;
+/// The range starts inside the flash, but runs past its end.
+pub const RANGE_PAST_END: u32 = GEOMETRY_BASE + 1;
+/// An erase address isn't at the start of a sector.
+pub const ADDRESS_NOT_SECTOR_ALIGNED: u32 = GEOMETRY_BASE + 2;
+/// A program address isn't at the start of a page.
+pub const ADDRESS_NOT_PAGE_ALIGNED: u32 = GEOMETRY_BASE + 3;
+/// More data than fits in a page.
+pub const LENGTH_EXCEEDS_PAGE: u32 = GEOMETRY_BASE + 4;
+
+pub const DECOMPRESSOR_BASE: u32 = 31000;
+/// More compressed data was sent than the image header announced.
+pub const DECOMPRESSOR_OVERRUN: u32 = DECOMPRESSOR_BASE;
+/// The first chunk of an image was too short to hold the length header.
+pub const DECOMPRESSOR_UNDERRUN: u32 = DECOMPRESSOR_BASE + 1;
+/// `miniz_oxide`'s `TINFLStatus` failures are reported as
+/// `INFLATE_BASE + (status + 4)`, the lowest status being -4.
+pub const INFLATE_BASE: u32 = DECOMPRESSOR_BASE + 100;
+pub const INFLATE_FAILED_CANNOT_MAKE_PROGRESS: u32 = INFLATE_BASE;
+pub const INFLATE_BAD_PARAM: u32 = INFLATE_BASE + 1;
+pub const INFLATE_ADLER32_MISMATCH: u32 = INFLATE_BASE + 2;
+pub const INFLATE_FAILED: u32 = INFLATE_BASE + 3;
+
+pub const CONFIG_BASE: u32 = 32000;
+/// The flash erases in larger units than the smallest sector in the sector
+/// map (`IMXRT_SECTOR_MAP`).
+pub const SECTOR_MAP_UNSUPPORTED: u32 = CONFIG_BASE;
+/// The flash is larger than 16 MB, but a command in the probed LUT has no
+/// 4-byte address form.
+pub const FOUR_BYTE_ADDRESS_UNSUPPORTED: u32 = CONFIG_BASE + 1;
+/// The flash's pages are smaller than the algorithm's (`PAGE_SIZE`), e.g.
+/// when parallel mode was configured for a single flash.
+pub const PAGE_SIZE_UNSUPPORTED: u32 = CONFIG_BASE + 2;
+/// A stacked-die part was found, but `FLASH_SIZE` is neither one die nor all
+/// of them.
+pub const STACKED_DIE_SIZE_MISMATCH: u32 = CONFIG_BASE + 3;
+/// The block protect bits can't be cleared, e.g. because the status register
+/// is protected and WP# is low.
+pub const PROTECTION_LOCKED: u32 = CONFIG_BASE + 4;
+/// A page programmed with quad page program read back wrong, with bits
+/// cleared that the data has set. The algorithm has switched back to the
+/// probed page program; the page has to be erased again.
+pub const QUAD_PROGRAM_FAILED: u32 = CONFIG_BASE + 5;
+
+pub const BOOT_BASE: u32 = 33000;
+/// The FCB at offset 0 doesn't parse.
+pub const BOOT_FCB_INVALID: u32 = BOOT_BASE;
+/// No IVT header (tag, length, version) at offset 0x1000.
+pub const BOOT_IVT_INVALID: u32 = BOOT_BASE + 1;
+/// The IVT's self pointer is inconsistent.
+pub const BOOT_IVT_SELF_INVALID: u32 = BOOT_BASE + 2;
+/// The boot data is outside the header, or doesn't cover the IVT.
+pub const BOOT_DATA_INVALID: u32 = BOOT_BASE + 3;
+/// The entry point is outside the image described by the boot data.
+pub const BOOT_ENTRY_INVALID: u32 = BOOT_BASE + 4;
+/// The DCD is outside the header or has a bad header.
+pub const BOOT_DCD_INVALID: u32 = BOOT_BASE + 5;
+
+/// Human readable description of an error code.
+// The known codes of each range come first, then the rest of it.
+#[allow(clippy::match_overlapping_arm)]
+pub fn message(code: u32) -> &'static str {
+    match code {
+        0 => "Success",
+
+        // ROM
+        1 => "ROM: failure",
+        4 => "ROM: invalid argument",
+        5 => "ROM: timeout",
+        7000 => "ROM: FlexSPI sequence execution timeout",
+        7001 => "ROM: FlexSPI invalid sequence",
+        7002 => "ROM: FlexSPI device timeout",
+        20100 => "ROM: page program failed",
+        20101 => "ROM: sector erase failed",
+        20102 => "ROM: chip erase failed",
+        20103 => "ROM: timeout waiting for the flash",
+        20104 => "ROM: not supported",
+        20105 => "ROM: write alignment error",
+        20106 => "ROM: command failure",
+        20107 => "ROM: SFDP table not found",
+        20109 => "ROM: flash not found",
+        20110 => "ROM: DTR read dummy cycle probe failed",
+        1..=ROM_LAST => "ROM: unknown status",
+
+        // Geometry
+        ADDRESS_OUT_OF_RANGE => "Address is outside of the flash",
+        RANGE_PAST_END => "Range runs past the end of the flash",
+        ADDRESS_NOT_SECTOR_ALIGNED => "Address is not at the start of a sector",
+        ADDRESS_NOT_PAGE_ALIGNED => "Address is not at the start of a page",
+        LENGTH_EXCEEDS_PAGE => "Data is longer than a page",
+        GEOMETRY_BASE..=30999 => "Unknown geometry error",
+
+        // Decompressor
+        DECOMPRESSOR_OVERRUN => "Decompressor: more data than announced in the image header",
+        DECOMPRESSOR_UNDERRUN => "Decompressor: first chunk too short for the image header",
+        INFLATE_FAILED_CANNOT_MAKE_PROGRESS => "Inflate: cannot make progress",
+        INFLATE_BAD_PARAM => "Inflate: bad parameter",
+        INFLATE_ADLER32_MISMATCH => "Inflate: Adler-32 checksum mismatch",
+        INFLATE_FAILED => "Inflate: corrupt stream",
+        DECOMPRESSOR_BASE..=31999 => "Unknown decompressor error",
+
+        // Configuration
+        SECTOR_MAP_UNSUPPORTED => "Sector map has sectors smaller than the flash can erase",
+        FOUR_BYTE_ADDRESS_UNSUPPORTED => "Flash command has no 4-byte address form",
+        PAGE_SIZE_UNSUPPORTED => "Flash pages are smaller than the algorithm's page size",
+        STACKED_DIE_SIZE_MISMATCH => "Flash size doesn't match the stacked-die part",
+        PROTECTION_LOCKED => "Block protection can't be cleared (status register protected?)",
+        QUAD_PROGRAM_FAILED => "Quad page program failed, erase the page and program again",
+        CONFIG_BASE..=32999 => "Unknown configuration error",
+
+        // Boot header
+        BOOT_FCB_INVALID => "Boot header: invalid FCB at offset 0",
+        BOOT_IVT_INVALID => "Boot header: no valid IVT at offset 0x1000",
+        BOOT_IVT_SELF_INVALID => "Boot header: inconsistent IVT self pointer",
+        BOOT_DATA_INVALID => "Boot header: boot data doesn't describe the image",
+        BOOT_ENTRY_INVALID => "Boot header: entry point outside of the image",
+        BOOT_DCD_INVALID => "Boot header: invalid DCD",
+        BOOT_BASE..=33999 => "Unknown boot header error",
+
+        _ => "Unknown error",
+    }
+}
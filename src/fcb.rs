@@ -0,0 +1,1255 @@
+//! FlexSPI Configuration Block (FCB): build, serialize and parse.
+//!
+//! The FCB is the 512 byte `flexspi_nor_config_t` the boot ROM reads from the
+//! start of a FlexSPI NOR flash. [`Builder`] makes one from a typed
+//! description, [`Fcb::to_bytes`] serializes it, and [`Fcb::parse`] reads and
+//! validates an existing binary, reporting the first offending field.
+//!
+//! This module doesn't depend on anything else in the crate, so firmware
+//! builds and host tooling can pull it in with
+//! `#[path = "…/src/fcb.rs"] mod fcb;` and emit the same FCB the flash
+//! algorithm uses (see `boot::load_fcb`).
+
+#![allow(dead_code)]
+
+/// Size of the serialized block.
+pub const SIZE: usize = 512;
+/// `tag`, "FCFB" in little endian.
+pub const TAG: u32 = 0x4246_4346;
+/// `version` written by [`Builder`]: 'V', major 1, minor 4, bugfix 0.
+pub const VERSION: u32 = 0x5601_0400;
+/// The top byte of every `version`.
+const VERSION_MAGIC: u32 = 0x56;
+const VERSION_MAJOR: u32 = 1;
+
+/// Number of LUT sequences, and instructions per sequence.
+pub const SEQUENCES: usize = 16;
+pub const INSTRUCTIONS: usize = 8;
+
+// Offsets into the serialized block.
+const TAG_OFFSET: usize = 0x000;
+const VERSION_OFFSET: usize = 0x004;
+const READ_SAMPLE_CLK_SRC: usize = 0x00C;
+const CS_HOLD_TIME: usize = 0x00D;
+const CS_SETUP_TIME: usize = 0x00E;
+const COLUMN_ADDRESS_WIDTH: usize = 0x00F;
+const DEVICE_MODE_CFG_ENABLE: usize = 0x010;
+const DEVICE_MODE_TYPE: usize = 0x011;
+const WAIT_TIME_CFG_COMMANDS: usize = 0x012;
+const DEVICE_MODE_SEQ: usize = 0x014;
+const DEVICE_MODE_ARG: usize = 0x018;
+const CONFIG_CMD_ENABLE: usize = 0x01C;
+const CONFIG_MODE_TYPE: usize = 0x01D;
+const CONFIG_CMD_SEQS: usize = 0x020;
+const CONFIG_CMD_ARGS: usize = 0x030;
+const CONTROLLER_MISC_OPTION: usize = 0x040;
+const DEVICE_TYPE: usize = 0x044;
+const SFLASH_PAD_TYPE: usize = 0x045;
+const SERIAL_CLK_FREQ: usize = 0x046;
+const LUT_CUSTOM_SEQ_ENABLE: usize = 0x047;
+const SFLASH_SIZES: usize = 0x050;
+const PAD_SETTING_OVERRIDES: usize = 0x060;
+const TIMEOUT_IN_MS: usize = 0x070;
+const COMMAND_INTERVAL: usize = 0x074;
+const DATA_VALID_TIME: usize = 0x078;
+const BUSY_OFFSET: usize = 0x07C;
+const BUSY_BIT_POLARITY: usize = 0x07E;
+const LOOKUP_TABLE: usize = 0x080;
+const LUT_CUSTOM_SEQ: usize = 0x180;
+const PAGE_SIZE: usize = 0x1C0;
+const SECTOR_SIZE: usize = 0x1C4;
+const IPCMD_SERIAL_CLK_FREQ: usize = 0x1C8;
+const IS_UNIFORM_BLOCK_SIZE: usize = 0x1C9;
+const SERIAL_NOR_TYPE: usize = 0x1CC;
+const NEED_EXIT_NO_CMD_MODE: usize = 0x1CD;
+const HALF_CLK_FOR_NON_READ_CMD: usize = 0x1CE;
+const NEED_RESTORE_NO_CMD_MODE: usize = 0x1CF;
+const BLOCK_SIZE: usize = 0x1D0;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum ReadSampleClock {
+    LoopbackInternally = 0,
+    LoopbackFromDqsPad = 1,
+    LoopbackFromSckPad = 2,
+    ExternalInputFromDqsPad = 3,
+}
+
+impl ReadSampleClock {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Self::LoopbackInternally,
+            1 => Self::LoopbackFromDqsPad,
+            2 => Self::LoopbackFromSckPad,
+            3 => Self::ExternalInputFromDqsPad,
+            _ => return None,
+        })
+    }
+}
+
+/// Number of data pads, in `sflashPadType` and in LUT instructions.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Pads {
+    Single = 1,
+    Dual = 2,
+    Quad = 4,
+    Octal = 8,
+}
+
+impl Pads {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => Self::Single,
+            2 => Self::Dual,
+            4 => Self::Quad,
+            8 => Self::Octal,
+            _ => return None,
+        })
+    }
+
+    /// Encoding in a LUT instruction.
+    const fn lut_bits(self) -> u16 {
+        match self {
+            Self::Single => 0,
+            Self::Dual => 1,
+            Self::Quad => 2,
+            Self::Octal => 3,
+        }
+    }
+
+    const fn from_lut_bits(bits: u16) -> Self {
+        match bits & 0b11 {
+            0 => Self::Single,
+            1 => Self::Dual,
+            2 => Self::Quad,
+            _ => Self::Octal,
+        }
+    }
+}
+
+/// `serialClkFreq` / `ipcmdSerialClkFreq`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum SerialClock {
+    Mhz30 = 1,
+    Mhz50 = 2,
+    Mhz60 = 3,
+    Mhz75 = 4,
+    Mhz80 = 5,
+    Mhz100 = 6,
+    Mhz120 = 7,
+    Mhz133 = 8,
+    Mhz166 = 9,
+}
+
+impl SerialClock {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => Self::Mhz30,
+            2 => Self::Mhz50,
+            3 => Self::Mhz60,
+            4 => Self::Mhz75,
+            5 => Self::Mhz80,
+            6 => Self::Mhz100,
+            7 => Self::Mhz120,
+            8 => Self::Mhz133,
+            9 => Self::Mhz166,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum DeviceType {
+    SerialNor = 1,
+    SerialNand = 2,
+}
+
+impl DeviceType {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => Self::SerialNor,
+            2 => Self::SerialNand,
+            _ => return None,
+        })
+    }
+}
+
+/// LUT instruction opcodes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Opcode {
+    Stop = 0x00,
+    CmdSdr = 0x01,
+    RaddrSdr = 0x02,
+    CaddrSdr = 0x03,
+    Mode1Sdr = 0x04,
+    Mode2Sdr = 0x05,
+    Mode4Sdr = 0x06,
+    Mode8Sdr = 0x07,
+    WriteSdr = 0x08,
+    ReadSdr = 0x09,
+    LearnSdr = 0x0A,
+    DataszSdr = 0x0B,
+    DummySdr = 0x0C,
+    DummyRwdsSdr = 0x0D,
+    JmpOnCs = 0x1F,
+    CmdDdr = 0x21,
+    RaddrDdr = 0x22,
+    CaddrDdr = 0x23,
+    Mode1Ddr = 0x24,
+    Mode2Ddr = 0x25,
+    Mode4Ddr = 0x26,
+    Mode8Ddr = 0x27,
+    WriteDdr = 0x28,
+    ReadDdr = 0x29,
+    LearnDdr = 0x2A,
+    DataszDdr = 0x2B,
+    DummyDdr = 0x2C,
+    DummyRwdsDdr = 0x2D,
+}
+
+impl Opcode {
+    fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0x00 => Self::Stop,
+            0x01 => Self::CmdSdr,
+            0x02 => Self::RaddrSdr,
+            0x03 => Self::CaddrSdr,
+            0x04 => Self::Mode1Sdr,
+            0x05 => Self::Mode2Sdr,
+            0x06 => Self::Mode4Sdr,
+            0x07 => Self::Mode8Sdr,
+            0x08 => Self::WriteSdr,
+            0x09 => Self::ReadSdr,
+            0x0A => Self::LearnSdr,
+            0x0B => Self::DataszSdr,
+            0x0C => Self::DummySdr,
+            0x0D => Self::DummyRwdsSdr,
+            0x1F => Self::JmpOnCs,
+            0x21 => Self::CmdDdr,
+            0x22 => Self::RaddrDdr,
+            0x23 => Self::CaddrDdr,
+            0x24 => Self::Mode1Ddr,
+            0x25 => Self::Mode2Ddr,
+            0x26 => Self::Mode4Ddr,
+            0x27 => Self::Mode8Ddr,
+            0x28 => Self::WriteDdr,
+            0x29 => Self::ReadDdr,
+            0x2A => Self::LearnDdr,
+            0x2B => Self::DataszDdr,
+            0x2C => Self::DummyDdr,
+            0x2D => Self::DummyRwdsDdr,
+            _ => return None,
+        })
+    }
+}
+
+/// One LUT instruction: opcode [15:10], pads [9:8], operand [7:0].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Instr(u16);
+
+impl Instr {
+    pub const STOP: Self = Self(0);
+
+    pub const fn new(opcode: Opcode, pads: Pads, operand: u8) -> Self {
+        Self((opcode as u16) << 10 | pads.lut_bits() << 8 | operand as u16)
+    }
+
+    pub fn opcode(self) -> Option<Opcode> {
+        Opcode::from_u8((self.0 >> 10) as u8)
+    }
+
+    pub const fn pads(self) -> Pads {
+        Pads::from_lut_bits(self.0 >> 8)
+    }
+
+    pub const fn operand(self) -> u8 {
+        self.0 as u8
+    }
+
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    pub const fn with_operand(self, operand: u8) -> Self {
+        Self(self.0 & !0xFF | operand as u16)
+    }
+}
+
+/// A LUT sequence, padded with [`Instr::STOP`].
+pub type Sequence = [Instr; INSTRUCTIONS];
+
+/// Build a [`Sequence`] from up to 8 instructions.
+pub const fn sequence(instrs: &[Instr]) -> Sequence {
+    let mut seq = [Instr::STOP; INSTRUCTIONS];
+    let mut i = 0;
+    while i < instrs.len() {
+        seq[i] = instrs[i];
+        i += 1;
+    }
+    seq
+}
+
+/// `flexspi_lut_seq_t`: `count` sequences starting at LUT index `index`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct SeqRef {
+    pub count: u8,
+    pub index: u8,
+}
+
+impl SeqRef {
+    fn from_u32(value: u32) -> Self {
+        Self {
+            count: value as u8,
+            index: (value >> 8) as u8,
+        }
+    }
+
+    fn to_u32(self) -> u32 {
+        self.count as u32 | (self.index as u32) << 8
+    }
+
+    fn valid(self) -> bool {
+        self.count == 0
+            || (self.index as usize) < SEQUENCES
+                && self.index as usize + self.count as usize <= SEQUENCES
+    }
+}
+
+/// One of the three `configCmd*` entries.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct ConfigCommand {
+    pub mode_type: u8,
+    pub seq: SeqRef,
+    pub arg: u32,
+}
+
+/// A FlexSPI NOR configuration block, field by field.
+///
+/// Reserved fields are always serialized as zero.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Fcb {
+    pub version: u32,
+    pub read_sample_clock: ReadSampleClock,
+    pub cs_hold_time: u8,
+    pub cs_setup_time: u8,
+    pub column_address_width: u8,
+    pub device_mode_enable: bool,
+    pub device_mode_type: u8,
+    /// Unit: 100us.
+    pub wait_time_cfg_commands: u16,
+    pub device_mode_seq: SeqRef,
+    pub device_mode_arg: u32,
+    pub config_command_enable: bool,
+    pub config_commands: [ConfigCommand; 3],
+    pub controller_misc_option: u32,
+    pub device_type: DeviceType,
+    pub pads: Pads,
+    pub serial_clock: SerialClock,
+    pub lut_custom_seq_enable: bool,
+    /// Flash sizes on A1, A2, B1 and B2.
+    pub flash_size: [u32; 4],
+    /// CS, SCK, data and DQS pad setting overrides.
+    pub pad_setting_override: [u32; 4],
+    pub timeout_ms: u32,
+    pub command_interval: u32,
+    /// Port A and B, unit: 0.1ns.
+    pub data_valid_time: [u16; 2],
+    pub busy_offset: u16,
+    pub busy_bit_polarity: u16,
+    pub lut: [Sequence; SEQUENCES],
+    pub lut_custom_seq: [SeqRef; 12],
+    pub page_size: u32,
+    pub sector_size: u32,
+    /// `None`: the ROM's default (30 MHz).
+    pub ipcmd_serial_clock: Option<SerialClock>,
+    pub is_uniform_block_size: bool,
+    pub serial_nor_type: u8,
+    pub need_exit_no_cmd_mode: bool,
+    pub half_clk_for_non_read_cmd: bool,
+    pub need_restore_no_cmd_mode: bool,
+    pub block_size: u32,
+}
+
+/// The field [`Fcb::parse`] or [`Builder::build`] rejected.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Field {
+    /// The binary isn't [`SIZE`] bytes long.
+    Length,
+    Tag,
+    Version,
+    ReadSampleClock,
+    DeviceModeSeq,
+    ConfigCommandSeq(u8),
+    DeviceType,
+    Pads,
+    SerialClock,
+    FlashSize,
+    BusyOffset,
+    BusyBitPolarity,
+    Lut {
+        sequence: u8,
+        instr: u8,
+    },
+    LutCustomSeq(u8),
+    PageSize,
+    SectorSize,
+    IpcmdSerialClock,
+    BlockSize,
+}
+
+impl Field {
+    /// Offset of the field in the serialized block.
+    pub fn offset(self) -> usize {
+        match self {
+            Self::Length => SIZE,
+            Self::Tag => TAG_OFFSET,
+            Self::Version => VERSION_OFFSET,
+            Self::ReadSampleClock => READ_SAMPLE_CLK_SRC,
+            Self::DeviceModeSeq => DEVICE_MODE_SEQ,
+            Self::ConfigCommandSeq(i) => CONFIG_CMD_SEQS + 4 * i as usize,
+            Self::DeviceType => DEVICE_TYPE,
+            Self::Pads => SFLASH_PAD_TYPE,
+            Self::SerialClock => SERIAL_CLK_FREQ,
+            Self::FlashSize => SFLASH_SIZES,
+            Self::BusyOffset => BUSY_OFFSET,
+            Self::BusyBitPolarity => BUSY_BIT_POLARITY,
+            Self::Lut { sequence, instr } => {
+                LOOKUP_TABLE + 16 * sequence as usize + 2 * instr as usize
+            }
+            Self::LutCustomSeq(i) => LUT_CUSTOM_SEQ + 4 * i as usize,
+            Self::PageSize => PAGE_SIZE,
+            Self::SectorSize => SECTOR_SIZE,
+            Self::IpcmdSerialClock => IPCMD_SERIAL_CLK_FREQ,
+            Self::BlockSize => BLOCK_SIZE,
+        }
+    }
+
+    /// Name of the field in `flexspi_nor_config_t`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Length => "length",
+            Self::Tag => "tag",
+            Self::Version => "version",
+            Self::ReadSampleClock => "readSampleClkSrc",
+            Self::DeviceModeSeq => "deviceModeSeq",
+            Self::ConfigCommandSeq(_) => "configCmdSeqs",
+            Self::DeviceType => "deviceType",
+            Self::Pads => "sflashPadType",
+            Self::SerialClock => "serialClkFreq",
+            Self::FlashSize => "sflashA1Size",
+            Self::BusyOffset => "busyOffset",
+            Self::BusyBitPolarity => "busyBitPolarity",
+            Self::Lut { .. } => "lookupTable",
+            Self::LutCustomSeq(_) => "lutCustomSeq",
+            Self::PageSize => "pageSize",
+            Self::SectorSize => "sectorSize",
+            Self::IpcmdSerialClock => "ipcmdSerialClkFreq",
+            Self::BlockSize => "blockSize",
+        }
+    }
+}
+
+/// A field with an invalid value.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Error {
+    pub field: Field,
+    pub value: u32,
+}
+
+fn invalid(field: Field, value: u32) -> Error {
+    Error { field, value }
+}
+
+impl Fcb {
+    /// Check the fields the enums don't already constrain.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.version >> 24 != VERSION_MAGIC || (self.version >> 16) & 0xFF != VERSION_MAJOR {
+            return Err(invalid(Field::Version, self.version));
+        }
+        if !self.device_mode_seq.valid() {
+            return Err(invalid(Field::DeviceModeSeq, self.device_mode_seq.to_u32()));
+        }
+        for (i, command) in self.config_commands.iter().enumerate() {
+            if !command.seq.valid() {
+                return Err(invalid(
+                    Field::ConfigCommandSeq(i as u8),
+                    command.seq.to_u32(),
+                ));
+            }
+        }
+        // The ROM boots from the flash on A1, alone or in parallel mode with
+        // the one on B1.
+        if self.flash_size[0] == 0 {
+            return Err(invalid(Field::FlashSize, 0));
+        }
+        if self.busy_offset > 31 {
+            return Err(invalid(Field::BusyOffset, self.busy_offset as u32));
+        }
+        if self.busy_bit_polarity > 1 {
+            return Err(invalid(
+                Field::BusyBitPolarity,
+                self.busy_bit_polarity as u32,
+            ));
+        }
+        for (sequence, seq) in self.lut.iter().enumerate() {
+            for (instr, op) in seq.iter().enumerate() {
+                if op.opcode().is_none() {
+                    let field = Field::Lut {
+                        sequence: sequence as u8,
+                        instr: instr as u8,
+                    };
+                    return Err(invalid(field, op.bits() as u32));
+                }
+            }
+        }
+        // Without a read sequence, nothing can be read through the AHB window.
+        if self.lut[0][0] == Instr::STOP {
+            let field = Field::Lut {
+                sequence: 0,
+                instr: 0,
+            };
+            return Err(invalid(field, 0));
+        }
+        for (i, seq) in self.lut_custom_seq.iter().enumerate() {
+            if !seq.valid() {
+                return Err(invalid(Field::LutCustomSeq(i as u8), seq.to_u32()));
+            }
+        }
+        if !self.page_size.is_power_of_two() {
+            return Err(invalid(Field::PageSize, self.page_size));
+        }
+        if !self.sector_size.is_power_of_two() || self.sector_size < self.page_size {
+            return Err(invalid(Field::SectorSize, self.sector_size));
+        }
+        if self.block_size & (self.sector_size - 1) != 0 {
+            return Err(invalid(Field::BlockSize, self.block_size));
+        }
+        Ok(())
+    }
+
+    /// Serialize into the layout the boot ROM reads.
+    pub fn to_bytes(self) -> [u8; SIZE] {
+        let mut out = [0u8; SIZE];
+        let mut put =
+            |offset: usize, bytes: &[u8]| out[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+        put(TAG_OFFSET, &TAG.to_le_bytes());
+        put(VERSION_OFFSET, &self.version.to_le_bytes());
+        put(READ_SAMPLE_CLK_SRC, &[self.read_sample_clock as u8]);
+        put(CS_HOLD_TIME, &[self.cs_hold_time]);
+        put(CS_SETUP_TIME, &[self.cs_setup_time]);
+        put(COLUMN_ADDRESS_WIDTH, &[self.column_address_width]);
+        put(DEVICE_MODE_CFG_ENABLE, &[self.device_mode_enable as u8]);
+        put(DEVICE_MODE_TYPE, &[self.device_mode_type]);
+        put(
+            WAIT_TIME_CFG_COMMANDS,
+            &self.wait_time_cfg_commands.to_le_bytes(),
+        );
+        put(
+            DEVICE_MODE_SEQ,
+            &self.device_mode_seq.to_u32().to_le_bytes(),
+        );
+        put(DEVICE_MODE_ARG, &self.device_mode_arg.to_le_bytes());
+        put(CONFIG_CMD_ENABLE, &[self.config_command_enable as u8]);
+        for (i, command) in self.config_commands.iter().enumerate() {
+            put(CONFIG_MODE_TYPE + i, &[command.mode_type]);
+            put(CONFIG_CMD_SEQS + 4 * i, &command.seq.to_u32().to_le_bytes());
+            put(CONFIG_CMD_ARGS + 4 * i, &command.arg.to_le_bytes());
+        }
+        put(
+            CONTROLLER_MISC_OPTION,
+            &self.controller_misc_option.to_le_bytes(),
+        );
+        put(DEVICE_TYPE, &[self.device_type as u8]);
+        put(SFLASH_PAD_TYPE, &[self.pads as u8]);
+        put(SERIAL_CLK_FREQ, &[self.serial_clock as u8]);
+        put(LUT_CUSTOM_SEQ_ENABLE, &[self.lut_custom_seq_enable as u8]);
+        for (i, size) in self.flash_size.iter().enumerate() {
+            put(SFLASH_SIZES + 4 * i, &size.to_le_bytes());
+        }
+        for (i, value) in self.pad_setting_override.iter().enumerate() {
+            put(PAD_SETTING_OVERRIDES + 4 * i, &value.to_le_bytes());
+        }
+        put(TIMEOUT_IN_MS, &self.timeout_ms.to_le_bytes());
+        put(COMMAND_INTERVAL, &self.command_interval.to_le_bytes());
+        put(DATA_VALID_TIME, &self.data_valid_time[0].to_le_bytes());
+        put(DATA_VALID_TIME + 2, &self.data_valid_time[1].to_le_bytes());
+        put(BUSY_OFFSET, &self.busy_offset.to_le_bytes());
+        put(BUSY_BIT_POLARITY, &self.busy_bit_polarity.to_le_bytes());
+        for (s, seq) in self.lut.iter().enumerate() {
+            for (i, instr) in seq.iter().enumerate() {
+                put(LOOKUP_TABLE + 16 * s + 2 * i, &instr.bits().to_le_bytes());
+            }
+        }
+        for (i, seq) in self.lut_custom_seq.iter().enumerate() {
+            put(LUT_CUSTOM_SEQ + 4 * i, &seq.to_u32().to_le_bytes());
+        }
+        put(PAGE_SIZE, &self.page_size.to_le_bytes());
+        put(SECTOR_SIZE, &self.sector_size.to_le_bytes());
+        put(
+            IPCMD_SERIAL_CLK_FREQ,
+            &[self.ipcmd_serial_clock.map_or(0, |clock| clock as u8)],
+        );
+        put(IS_UNIFORM_BLOCK_SIZE, &[self.is_uniform_block_size as u8]);
+        put(SERIAL_NOR_TYPE, &[self.serial_nor_type]);
+        put(NEED_EXIT_NO_CMD_MODE, &[self.need_exit_no_cmd_mode as u8]);
+        put(
+            HALF_CLK_FOR_NON_READ_CMD,
+            &[self.half_clk_for_non_read_cmd as u8],
+        );
+        put(
+            NEED_RESTORE_NO_CMD_MODE,
+            &[self.need_restore_no_cmd_mode as u8],
+        );
+        put(BLOCK_SIZE, &self.block_size.to_le_bytes());
+
+        out
+    }
+
+    /// Parse and validate a serialized block.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != SIZE {
+            return Err(invalid(Field::Length, bytes.len() as u32));
+        }
+        let u8_at = |offset: usize| bytes[offset];
+        let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        let tag = u32_at(TAG_OFFSET);
+        if tag != TAG {
+            return Err(invalid(Field::Tag, tag));
+        }
+        let read_sample_clock = u8_at(READ_SAMPLE_CLK_SRC);
+        let read_sample_clock = ReadSampleClock::from_u8(read_sample_clock)
+            .ok_or(invalid(Field::ReadSampleClock, read_sample_clock as u32))?;
+        let device_type = u8_at(DEVICE_TYPE);
+        let device_type = DeviceType::from_u8(device_type)
+            .ok_or(invalid(Field::DeviceType, device_type as u32))?;
+        let pads = u8_at(SFLASH_PAD_TYPE);
+        let pads = Pads::from_u8(pads).ok_or(invalid(Field::Pads, pads as u32))?;
+        let serial_clock = u8_at(SERIAL_CLK_FREQ);
+        let serial_clock = SerialClock::from_u8(serial_clock)
+            .ok_or(invalid(Field::SerialClock, serial_clock as u32))?;
+        let ipcmd_serial_clock = match u8_at(IPCMD_SERIAL_CLK_FREQ) {
+            0 => None,
+            clock => Some(
+                SerialClock::from_u8(clock)
+                    .ok_or(invalid(Field::IpcmdSerialClock, clock as u32))?,
+            ),
+        };
+
+        let mut config_commands = [ConfigCommand::default(); 3];
+        for (i, command) in config_commands.iter_mut().enumerate() {
+            *command = ConfigCommand {
+                mode_type: u8_at(CONFIG_MODE_TYPE + i),
+                seq: SeqRef::from_u32(u32_at(CONFIG_CMD_SEQS + 4 * i)),
+                arg: u32_at(CONFIG_CMD_ARGS + 4 * i),
+            };
+        }
+        let mut lut = [[Instr::STOP; INSTRUCTIONS]; SEQUENCES];
+        for (s, seq) in lut.iter_mut().enumerate() {
+            for (i, instr) in seq.iter_mut().enumerate() {
+                *instr = Instr(u16_at(LOOKUP_TABLE + 16 * s + 2 * i));
+            }
+        }
+        let mut lut_custom_seq = [SeqRef::default(); 12];
+        for (i, seq) in lut_custom_seq.iter_mut().enumerate() {
+            *seq = SeqRef::from_u32(u32_at(LUT_CUSTOM_SEQ + 4 * i));
+        }
+
+        let fcb = Self {
+            version: u32_at(VERSION_OFFSET),
+            read_sample_clock,
+            cs_hold_time: u8_at(CS_HOLD_TIME),
+            cs_setup_time: u8_at(CS_SETUP_TIME),
+            column_address_width: u8_at(COLUMN_ADDRESS_WIDTH),
+            device_mode_enable: u8_at(DEVICE_MODE_CFG_ENABLE) != 0,
+            device_mode_type: u8_at(DEVICE_MODE_TYPE),
+            wait_time_cfg_commands: u16_at(WAIT_TIME_CFG_COMMANDS),
+            device_mode_seq: SeqRef::from_u32(u32_at(DEVICE_MODE_SEQ)),
+            device_mode_arg: u32_at(DEVICE_MODE_ARG),
+            config_command_enable: u8_at(CONFIG_CMD_ENABLE) != 0,
+            config_commands,
+            controller_misc_option: u32_at(CONTROLLER_MISC_OPTION),
+            device_type,
+            pads,
+            serial_clock,
+            lut_custom_seq_enable: u8_at(LUT_CUSTOM_SEQ_ENABLE) != 0,
+            flash_size: [
+                u32_at(SFLASH_SIZES),
+                u32_at(SFLASH_SIZES + 4),
+                u32_at(SFLASH_SIZES + 8),
+                u32_at(SFLASH_SIZES + 12),
+            ],
+            pad_setting_override: [
+                u32_at(PAD_SETTING_OVERRIDES),
+                u32_at(PAD_SETTING_OVERRIDES + 4),
+                u32_at(PAD_SETTING_OVERRIDES + 8),
+                u32_at(PAD_SETTING_OVERRIDES + 12),
+            ],
+            timeout_ms: u32_at(TIMEOUT_IN_MS),
+            command_interval: u32_at(COMMAND_INTERVAL),
+            data_valid_time: [u16_at(DATA_VALID_TIME), u16_at(DATA_VALID_TIME + 2)],
+            busy_offset: u16_at(BUSY_OFFSET),
+            busy_bit_polarity: u16_at(BUSY_BIT_POLARITY),
+            lut,
+            lut_custom_seq,
+            page_size: u32_at(PAGE_SIZE),
+            sector_size: u32_at(SECTOR_SIZE),
+            ipcmd_serial_clock,
+            is_uniform_block_size: u8_at(IS_UNIFORM_BLOCK_SIZE) != 0,
+            serial_nor_type: u8_at(SERIAL_NOR_TYPE),
+            need_exit_no_cmd_mode: u8_at(NEED_EXIT_NO_CMD_MODE) != 0,
+            half_clk_for_non_read_cmd: u8_at(HALF_CLK_FOR_NON_READ_CMD) != 0,
+            need_restore_no_cmd_mode: u8_at(NEED_RESTORE_NO_CMD_MODE) != 0,
+            block_size: u32_at(BLOCK_SIZE),
+        };
+        fcb.validate()?;
+        Ok(fcb)
+    }
+}
+
+/// LUT sequence indices the ROM's NOR driver uses (`NOR_CMD_LUT_SEQ_IDX_*`).
+pub mod seq {
+    pub const READ: usize = 0;
+    pub const READSTATUS: usize = 1;
+    pub const WRITEENABLE: usize = 3;
+    pub const ERASESECTOR: usize = 5;
+    pub const ERASEBLOCK: usize = 8;
+    pub const PAGEPROGRAM: usize = 9;
+    pub const CHIPERASE: usize = 11;
+
+    /// The sequences that send an address.
+    pub const ADDRESSED: [usize; 4] = [READ, ERASESECTOR, ERASEBLOCK, PAGEPROGRAM];
+}
+
+/// Size of the flash 3-byte addresses reach.
+pub const ADDRESS_3BYTE_LIMIT: u32 = 16 * 1024 * 1024;
+
+/// The 4-byte address form of a command that takes a 3-byte address, from
+/// the JESD216 4-byte address instruction table.
+pub fn four_byte_command(command: u8) -> Option<u8> {
+    Some(match command {
+        0x03 => 0x13, // Read
+        0x0B => 0x0C, // Fast Read
+        0x3B => 0x3C, // Dual Output Read
+        0xBB => 0xBC, // Dual I/O Read
+        0x6B => 0x6C, // Quad Output Read
+        0xEB => 0xEC, // Quad I/O Read
+        0x0D => 0x0E, // DTR Fast Read
+        0xBD => 0xBE, // DTR Dual I/O Read
+        0xED => 0xEE, // DTR Quad I/O Read
+        0x02 => 0x12, // Page Program
+        0x32 => 0x34, // Quad Input Page Program
+        0x38 => 0x3E, // Quad I/O Page Program
+        0x20 => 0x21, // 4 KB Sector Erase
+        0x52 => 0x5C, // 32 KB Block Erase
+        0xD8 => 0xDC, // 64 KB Block Erase
+        _ => return None,
+    })
+}
+
+/// Switch `seq` from a 3-byte to a 4-byte address: the command becomes its
+/// [`four_byte_command`] and the address 32 bits wide. Sequences without a
+/// 3-byte address are left alone. Fails with the command if it has no 4-byte
+/// form.
+pub fn four_byte_addresses(seq: &mut Sequence) -> Result<(), u8> {
+    let is_raddr = |instr: &Instr| {
+        matches!(instr.opcode(), Some(Opcode::RaddrSdr | Opcode::RaddrDdr)) && instr.operand() == 24
+    };
+    let raddr = match seq.iter().position(is_raddr) {
+        Some(raddr) => raddr,
+        None => return Ok(()),
+    };
+    let cmd = match seq[..raddr]
+        .iter()
+        .position(|instr| matches!(instr.opcode(), Some(Opcode::CmdSdr | Opcode::CmdDdr)))
+    {
+        Some(cmd) => cmd,
+        None => return Ok(()),
+    };
+
+    let command = seq[cmd].operand();
+    let four_byte = four_byte_command(command).ok_or(command)?;
+    seq[cmd] = seq[cmd].with_operand(four_byte);
+    seq[raddr] = seq[raddr].with_operand(32);
+    Ok(())
+}
+
+/// LUT sequence `index` of a `lookupTable` as the ROM keeps it: two
+/// instructions per word, the first one in the low half.
+pub fn lut_sequence(lut: &[u32; SEQUENCES * INSTRUCTIONS / 2], index: usize) -> Sequence {
+    let mut seq = [Instr::STOP; INSTRUCTIONS];
+    for (i, instr) in seq.iter_mut().enumerate() {
+        let word = lut[index * INSTRUCTIONS / 2 + i / 2];
+        *instr = Instr((word >> (16 * (i % 2))) as u16);
+    }
+    seq
+}
+
+/// Store `seq` as LUT sequence `index`, see [`lut_sequence`].
+pub fn set_lut_sequence(
+    lut: &mut [u32; SEQUENCES * INSTRUCTIONS / 2],
+    index: usize,
+    seq: &Sequence,
+) {
+    let start = index * INSTRUCTIONS / 2;
+    lut[start..start + INSTRUCTIONS / 2].copy_from_slice(&lut_words(seq));
+}
+
+/// Where LUT sequence `index` starts in a `lookupTable`, for
+/// `flexspi_update_lut`.
+pub fn lut_sequence_ptr(lut: &[u32; SEQUENCES * INSTRUCTIONS / 2], index: usize) -> *const u32 {
+    lut[index * INSTRUCTIONS / 2..].as_ptr()
+}
+
+/// `seq` as the LUT words `flexspi_update_lut` takes, see [`lut_sequence`].
+pub fn lut_words(seq: &Sequence) -> [u32; INSTRUCTIONS / 2] {
+    let mut words = [0; INSTRUCTIONS / 2];
+    for (word, pair) in words.iter_mut().zip(seq.chunks_exact(2)) {
+        *word = pair[0].bits() as u32 | (pair[1].bits() as u32) << 16;
+    }
+    words
+}
+
+/// Builds a validated [`Fcb`].
+pub struct Builder {
+    fcb: Fcb,
+}
+
+impl Builder {
+    /// A single pad, 30 MHz serial NOR flash of `flash_size` bytes, with
+    /// 256 byte pages, 4 KB sectors and an empty LUT.
+    pub fn new(flash_size: u32) -> Self {
+        Self {
+            fcb: Fcb {
+                version: VERSION,
+                read_sample_clock: ReadSampleClock::LoopbackInternally,
+                cs_hold_time: 3,
+                cs_setup_time: 3,
+                column_address_width: 0,
+                device_mode_enable: false,
+                device_mode_type: 0,
+                wait_time_cfg_commands: 0,
+                device_mode_seq: SeqRef::default(),
+                device_mode_arg: 0,
+                config_command_enable: false,
+                config_commands: [ConfigCommand::default(); 3],
+                controller_misc_option: 0,
+                device_type: DeviceType::SerialNor,
+                pads: Pads::Single,
+                serial_clock: SerialClock::Mhz30,
+                lut_custom_seq_enable: false,
+                flash_size: [flash_size, 0, 0, 0],
+                pad_setting_override: [0; 4],
+                timeout_ms: 0,
+                command_interval: 0,
+                data_valid_time: [0; 2],
+                busy_offset: 0,
+                busy_bit_polarity: 0,
+                lut: [[Instr::STOP; INSTRUCTIONS]; SEQUENCES],
+                lut_custom_seq: [SeqRef::default(); 12],
+                page_size: 256,
+                sector_size: 4 * 1024,
+                ipcmd_serial_clock: None,
+                is_uniform_block_size: false,
+                serial_nor_type: 0,
+                need_exit_no_cmd_mode: false,
+                half_clk_for_non_read_cmd: false,
+                need_restore_no_cmd_mode: false,
+                block_size: 0,
+            },
+        }
+    }
+
+    /// A QuadSPI NOR flash read with Fast Read Quad I/O (EBh) at 100 MHz,
+    /// with the usual 1-pad status, write enable, erase and program
+    /// commands: the configuration the algorithm probes for by default.
+    ///
+    /// The flash's Quad Enable bit has to be set already (it's
+    /// non-volatile on most parts), or configured with [`Builder::device_mode`].
+    /// Flashes larger than 16 MB use the 4-byte address commands.
+    pub fn quad_spi_nor(flash_size: u32) -> Self {
+        use Opcode::*;
+        use Pads::*;
+
+        let mut builder = Self::new(flash_size)
+            .read_sample_clock(ReadSampleClock::LoopbackFromDqsPad)
+            .pads(Quad)
+            .serial_clock(SerialClock::Mhz100)
+            .block_size(64 * 1024)
+            .sequence(
+                seq::READ,
+                sequence(&[
+                    Instr::new(CmdSdr, Single, 0xEB),
+                    Instr::new(RaddrSdr, Quad, 24),
+                    Instr::new(DummySdr, Quad, 6),
+                    Instr::new(ReadSdr, Quad, 4),
+                ]),
+            )
+            .sequence(
+                seq::READSTATUS,
+                sequence(&[
+                    Instr::new(CmdSdr, Single, 0x05),
+                    Instr::new(ReadSdr, Single, 4),
+                ]),
+            )
+            .sequence(
+                seq::WRITEENABLE,
+                sequence(&[Instr::new(CmdSdr, Single, 0x06)]),
+            )
+            .sequence(
+                seq::ERASESECTOR,
+                sequence(&[
+                    Instr::new(CmdSdr, Single, 0x20),
+                    Instr::new(RaddrSdr, Single, 24),
+                ]),
+            )
+            .sequence(
+                seq::ERASEBLOCK,
+                sequence(&[
+                    Instr::new(CmdSdr, Single, 0xD8),
+                    Instr::new(RaddrSdr, Single, 24),
+                ]),
+            )
+            .sequence(
+                seq::PAGEPROGRAM,
+                sequence(&[
+                    Instr::new(CmdSdr, Single, 0x02),
+                    Instr::new(RaddrSdr, Single, 24),
+                    Instr::new(WriteSdr, Single, 4),
+                ]),
+            )
+            .sequence(
+                seq::CHIPERASE,
+                sequence(&[Instr::new(CmdSdr, Single, 0x60)]),
+            );
+
+        if flash_size > ADDRESS_3BYTE_LIMIT {
+            for index in seq::ADDRESSED {
+                // All of the commands above have a 4-byte form.
+                let _ = four_byte_addresses(&mut builder.fcb.lut[index]);
+            }
+        }
+        builder
+    }
+
+    pub fn read_sample_clock(mut self, clock: ReadSampleClock) -> Self {
+        self.fcb.read_sample_clock = clock;
+        self
+    }
+
+    pub fn cs_timing(mut self, hold: u8, setup: u8) -> Self {
+        self.fcb.cs_hold_time = hold;
+        self.fcb.cs_setup_time = setup;
+        self
+    }
+
+    pub fn pads(mut self, pads: Pads) -> Self {
+        self.fcb.pads = pads;
+        self
+    }
+
+    pub fn serial_clock(mut self, clock: SerialClock) -> Self {
+        self.fcb.serial_clock = clock;
+        self
+    }
+
+    pub fn ipcmd_serial_clock(mut self, clock: SerialClock) -> Self {
+        self.fcb.ipcmd_serial_clock = Some(clock);
+        self
+    }
+
+    pub fn page_size(mut self, size: u32) -> Self {
+        self.fcb.page_size = size;
+        self
+    }
+
+    pub fn sector_size(mut self, size: u32) -> Self {
+        self.fcb.sector_size = size;
+        self
+    }
+
+    pub fn block_size(mut self, size: u32) -> Self {
+        self.fcb.block_size = size;
+        self
+    }
+
+    pub fn uniform_block_size(mut self, uniform: bool) -> Self {
+        self.fcb.is_uniform_block_size = uniform;
+        self
+    }
+
+    pub fn busy_bit(mut self, offset: u16, polarity: u16) -> Self {
+        self.fcb.busy_offset = offset;
+        self.fcb.busy_bit_polarity = polarity;
+        self
+    }
+
+    /// Run LUT sequence `seq` with `arg` when the ROM configures the flash,
+    /// e.g. to set the Quad Enable bit.
+    pub fn device_mode(mut self, mode_type: u8, seq: SeqRef, arg: u32) -> Self {
+        self.fcb.device_mode_enable = true;
+        self.fcb.device_mode_type = mode_type;
+        self.fcb.device_mode_seq = seq;
+        self.fcb.device_mode_arg = arg;
+        self
+    }
+
+    pub fn sequence(mut self, index: usize, seq: Sequence) -> Self {
+        self.fcb.lut[index] = seq;
+        self
+    }
+
+    /// The description so far, for fields without a setter.
+    pub fn fcb_mut(&mut self) -> &mut Fcb {
+        &mut self.fcb
+    }
+
+    pub fn build(self) -> Result<Fcb, Error> {
+        self.fcb.validate()?;
+        Ok(self.fcb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLASH_SIZE: u32 = 8 * 1024 * 1024;
+
+    /// A block with every field away from its [`Builder::new`] default.
+    fn everything() -> Fcb {
+        let mut builder = Builder::quad_spi_nor(FLASH_SIZE)
+            .cs_timing(1, 2)
+            .ipcmd_serial_clock(SerialClock::Mhz50)
+            .page_size(512)
+            .sector_size(256 * 1024)
+            .block_size(256 * 1024)
+            .uniform_block_size(true)
+            .busy_bit(8, 1)
+            .device_mode(1, SeqRef { count: 1, index: 4 }, 0x0200);
+        let fcb = builder.fcb_mut();
+        fcb.column_address_width = 3;
+        fcb.wait_time_cfg_commands = 10;
+        fcb.config_command_enable = true;
+        fcb.config_commands[2] = ConfigCommand {
+            mode_type: 2,
+            seq: SeqRef { count: 2, index: 6 },
+            arg: 0xDEAD_BEEF,
+        };
+        fcb.controller_misc_option = 1 << 2;
+        fcb.lut_custom_seq_enable = true;
+        fcb.flash_size = [FLASH_SIZE, 0, FLASH_SIZE, 0];
+        fcb.pad_setting_override = [1, 2, 3, 4];
+        fcb.timeout_ms = 100;
+        fcb.command_interval = 5;
+        fcb.data_valid_time = [16, 17];
+        fcb.lut_custom_seq[11] = SeqRef {
+            count: 1,
+            index: 15,
+        };
+        fcb.serial_nor_type = 1;
+        fcb.need_exit_no_cmd_mode = true;
+        fcb.half_clk_for_non_read_cmd = true;
+        fcb.need_restore_no_cmd_mode = true;
+        builder.build().unwrap()
+    }
+
+    fn error(field: Field, value: u32) -> Result<Fcb, Error> {
+        Err(Error { field, value })
+    }
+
+    fn put_u32(bytes: &mut [u8; SIZE], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trip() {
+        for fcb in [
+            Builder::quad_spi_nor(FLASH_SIZE).build().unwrap(),
+            Builder::quad_spi_nor(64 * 1024 * 1024).build().unwrap(),
+            everything(),
+        ] {
+            assert_eq!(Fcb::parse(&fcb.to_bytes()), Ok(fcb));
+        }
+    }
+
+    #[test]
+    fn serialized_layout() {
+        let bytes = Builder::quad_spi_nor(FLASH_SIZE)
+            .build()
+            .unwrap()
+            .to_bytes();
+        assert_eq!(&bytes[..4], b"FCFB");
+        assert_eq!(bytes[VERSION_OFFSET + 3], b'V');
+        assert_eq!(bytes[SFLASH_PAD_TYPE], 4);
+        assert_eq!(bytes[SERIAL_CLK_FREQ], 6);
+        assert_eq!(
+            &bytes[SFLASH_SIZES..SFLASH_SIZES + 4],
+            &FLASH_SIZE.to_le_bytes()
+        );
+        // EBh on one pad, then a 24 bit address on four
+        assert_eq!(
+            &bytes[LOOKUP_TABLE..LOOKUP_TABLE + 4],
+            &[0xEB, 0x04, 0x18, 0x0A]
+        );
+    }
+
+    #[test]
+    fn four_byte_flash() {
+        let fcb = Builder::quad_spi_nor(64 * 1024 * 1024).build().unwrap();
+        assert_eq!(fcb.lut[seq::READ][0].operand(), 0xEC);
+        assert_eq!(fcb.lut[seq::READ][1].operand(), 32);
+        assert_eq!(fcb.lut[seq::PAGEPROGRAM][0].operand(), 0x12);
+        // Not addressed
+        assert_eq!(fcb.lut[seq::READSTATUS][0].operand(), 0x05);
+    }
+
+    #[test]
+    fn parse_rejects_bad_encodings() {
+        let good = everything().to_bytes();
+
+        assert_eq!(Fcb::parse(&good[..SIZE - 1]), error(Field::Length, 511));
+
+        let mut bytes = good;
+        bytes[0] = b'X';
+        assert_eq!(
+            Fcb::parse(&bytes),
+            error(Field::Tag, u32::from_le_bytes(*b"XCFB"))
+        );
+
+        for (field, value) in [
+            (Field::ReadSampleClock, 4),
+            (Field::DeviceType, 0),
+            (Field::Pads, 3),
+            (Field::SerialClock, 10),
+            (Field::IpcmdSerialClock, 10),
+        ] {
+            let mut bytes = good;
+            bytes[field.offset()] = value as u8;
+            assert_eq!(Fcb::parse(&bytes), error(field, value), "{}", field.name());
+        }
+    }
+
+    #[test]
+    fn parse_validates() {
+        let mut bytes = everything().to_bytes();
+        put_u32(&mut bytes, SFLASH_SIZES, 0);
+        assert_eq!(Fcb::parse(&bytes), error(Field::FlashSize, 0));
+    }
+
+    #[test]
+    fn validate_rejects_bad_fields() {
+        let sequence = |sequence, instr| Field::Lut { sequence, instr };
+        type Case = (fn(&mut Fcb), Field, u32);
+        let cases: [Case; 12] = [
+            (|fcb| fcb.version = 0x4601_0400, Field::Version, 0x4601_0400),
+            (|fcb| fcb.version = 0x5602_0000, Field::Version, 0x5602_0000),
+            (
+                |fcb| {
+                    fcb.device_mode_seq = SeqRef {
+                        count: 2,
+                        index: 15,
+                    }
+                },
+                Field::DeviceModeSeq,
+                0x0F02,
+            ),
+            (
+                |fcb| {
+                    fcb.config_commands[1].seq = SeqRef {
+                        count: 1,
+                        index: 16,
+                    }
+                },
+                Field::ConfigCommandSeq(1),
+                0x1001,
+            ),
+            (|fcb| fcb.flash_size[0] = 0, Field::FlashSize, 0),
+            (|fcb| fcb.busy_offset = 32, Field::BusyOffset, 32),
+            (|fcb| fcb.busy_bit_polarity = 2, Field::BusyBitPolarity, 2),
+            (
+                |fcb| fcb.lut[seq::READ] = [Instr::STOP; INSTRUCTIONS],
+                sequence(0, 0),
+                0,
+            ),
+            (
+                |fcb| {
+                    fcb.lut_custom_seq[3] = SeqRef {
+                        count: 17,
+                        index: 0,
+                    }
+                },
+                Field::LutCustomSeq(3),
+                17,
+            ),
+            (|fcb| fcb.page_size = 384, Field::PageSize, 384),
+            (|fcb| fcb.sector_size = 128, Field::SectorSize, 128),
+            (
+                |fcb| fcb.block_size = 96 * 1024,
+                Field::BlockSize,
+                96 * 1024,
+            ),
+        ];
+        for (break_it, field, value) in cases {
+            let mut fcb = everything();
+            break_it(&mut fcb);
+            assert_eq!(
+                fcb.validate(),
+                Err(Error { field, value }),
+                "{}",
+                field.name()
+            );
+        }
+    }
+
+    #[test]
+    fn validate_rejects_unknown_opcodes() {
+        let mut bytes = everything().to_bytes();
+        let field = Field::Lut {
+            sequence: seq::PAGEPROGRAM as u8,
+            instr: 5,
+        };
+        let bad = 0x0E << 10 | 0x12;
+        bytes[field.offset()..field.offset() + 2].copy_from_slice(&(bad as u16).to_le_bytes());
+        assert_eq!(Fcb::parse(&bytes), error(field, bad));
+    }
+
+    #[test]
+    fn field_offsets() {
+        assert_eq!(Field::ConfigCommandSeq(2).offset(), 0x028);
+        assert_eq!(
+            Field::Lut {
+                sequence: 9,
+                instr: 2
+            }
+            .offset(),
+            0x114
+        );
+        assert_eq!(Field::LutCustomSeq(11).offset(), 0x1AC);
+        assert_eq!(Field::BlockSize.offset(), 0x1D0);
+    }
+
+    #[test]
+    fn lut_words_round_trip() {
+        let mut lut = [0u32; SEQUENCES * INSTRUCTIONS / 2];
+        let seq = Builder::quad_spi_nor(FLASH_SIZE).build().unwrap().lut[seq::READ];
+        set_lut_sequence(&mut lut, 10, &seq);
+        assert_eq!(lut_sequence(&lut, 10), seq);
+        assert_eq!(lut[40], 0x0A18_04EB);
+        assert_eq!(lut_sequence_ptr(&lut, 10), &lut[40] as *const u32);
+        assert!(lut[..40].iter().chain(&lut[44..]).all(|&word| word == 0));
+    }
+}
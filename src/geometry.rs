@@ -0,0 +1,285 @@
+//! Address and length checks for every entry point.
+//!
+//! Each check turns an address in the FlexSPI window into an offset into the
+//! flash, or fails with one of the `error::GEOMETRY_*` codes, before anything
+//! reaches the ROM driver.
+//!
+//! Besides `error.rs`, this module doesn't depend on anything else in the
+//! crate, so it can be exercised on the host with both pulled in via
+//! `#[path]`.
+
+#![allow(dead_code)]
+
+use crate::error::*;
+
+/// A region of equally sized sectors, from `address` (an offset into the
+/// flash) up to the next region or the end of the flash.
+#[derive(Copy, Clone, Debug)]
+pub struct Sector {
+    /// A power of two.
+    pub size: u32,
+    pub address: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Geometry {
+    /// Start of the flash in the memory map.
+    pub start: u32,
+    pub size: u32,
+    /// In ascending order, the first one at 0.
+    pub sectors: &'static [Sector],
+    /// A power of two.
+    pub page_size: u32,
+}
+
+impl Geometry {
+    /// Offset of `address` into the flash, for a range of `len` bytes that
+    /// has to lie inside the flash.
+    pub fn range(&self, address: u32, len: u32) -> Result<u32, u32> {
+        let offset = match address.checked_sub(self.start) {
+            Some(offset) if offset < self.size => offset,
+            _ => return Err(ADDRESS_OUT_OF_RANGE),
+        };
+        if len > self.size - offset {
+            return Err(RANGE_PAST_END);
+        }
+        Ok(offset)
+    }
+
+    /// Offset and size of the sector at `address`.
+    pub fn sector(&self, address: u32) -> Result<(u32, u32), u32> {
+        let offset = self.range(address, 0)?;
+        let region = self
+            .sectors
+            .iter()
+            .rev()
+            .find(|region| region.address <= offset)
+            .ok_or(ADDRESS_OUT_OF_RANGE)?;
+        if (offset - region.address) & (region.size - 1) != 0 {
+            return Err(ADDRESS_NOT_SECTOR_ALIGNED);
+        }
+        Ok((self.range(address, region.size)?, region.size))
+    }
+
+    /// Size of the smallest sector.
+    pub fn min_sector_size(&self) -> u32 {
+        self.sectors
+            .iter()
+            .map(|region| region.size)
+            .min()
+            .unwrap_or(self.size)
+    }
+
+    /// Whether the regions are in ascending order, start at 0, end inside the
+    /// flash and are made of whole sectors. Meant for a `const` assertion.
+    pub const fn valid(&self) -> bool {
+        if self.sectors.is_empty() || self.sectors[0].address != 0 {
+            return false;
+        }
+        let mut i = 0;
+        while i < self.sectors.len() {
+            let region = self.sectors[i];
+            let end = if i + 1 < self.sectors.len() {
+                self.sectors[i + 1].address
+            } else {
+                self.size
+            };
+            if !region.size.is_power_of_two()
+                || end <= region.address
+                || (end - region.address) & (region.size - 1) != 0
+            {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    /// Offset of the page at `address`, for `len` bytes of it.
+    pub fn page(&self, address: u32, len: u32) -> Result<u32, u32> {
+        let offset = self.range(address, 0)?;
+        if offset & (self.page_size - 1) != 0 {
+            return Err(ADDRESS_NOT_PAGE_ALIGNED);
+        }
+        if len > self.page_size {
+            return Err(LENGTH_EXCEEDS_PAGE);
+        }
+        self.range(address, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u32 = 0x6000_0000;
+    const SIZE: u32 = 0x80_0000;
+
+    const UNIFORM: Geometry = Geometry {
+        start: START,
+        size: SIZE,
+        sectors: &[Sector {
+            size: 0x1_0000,
+            address: 0,
+        }],
+        page_size: 256,
+    };
+
+    /// 4 KB parameter sectors at the bottom, 64 KB above.
+    const BOTTOM_4K: Geometry = Geometry {
+        sectors: &[
+            Sector {
+                size: 0x1000,
+                address: 0,
+            },
+            Sector {
+                size: 0x1_0000,
+                address: 0x2_0000,
+            },
+        ],
+        ..UNIFORM
+    };
+
+    #[test]
+    fn range() {
+        assert_eq!(UNIFORM.range(START, 0), Ok(0));
+        assert_eq!(UNIFORM.range(START + 0x1234, 16), Ok(0x1234));
+        assert_eq!(UNIFORM.range(START, SIZE), Ok(0));
+        assert_eq!(UNIFORM.range(START + SIZE - 1, 1), Ok(SIZE - 1));
+    }
+
+    #[test]
+    fn address_below_the_window() {
+        assert_eq!(UNIFORM.range(START - 1, 0), Err(ADDRESS_OUT_OF_RANGE));
+        assert_eq!(UNIFORM.range(0, 0), Err(ADDRESS_OUT_OF_RANGE));
+        assert_eq!(UNIFORM.sector(START - 0x1_0000), Err(ADDRESS_OUT_OF_RANGE));
+        assert_eq!(UNIFORM.page(START - 256, 256), Err(ADDRESS_OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn address_past_the_flash() {
+        assert_eq!(UNIFORM.range(START + SIZE, 0), Err(ADDRESS_OUT_OF_RANGE));
+        assert_eq!(UNIFORM.range(u32::MAX, 0), Err(ADDRESS_OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn range_past_the_end() {
+        assert_eq!(UNIFORM.range(START, SIZE + 1), Err(RANGE_PAST_END));
+        assert_eq!(UNIFORM.range(START + SIZE - 4, 5), Err(RANGE_PAST_END));
+        // No overflow
+        assert_eq!(UNIFORM.range(START + 4, u32::MAX), Err(RANGE_PAST_END));
+    }
+
+    #[test]
+    fn sector() {
+        assert_eq!(UNIFORM.sector(START), Ok((0, 0x1_0000)));
+        assert_eq!(
+            UNIFORM.sector(START + SIZE - 0x1_0000),
+            Ok((SIZE - 0x1_0000, 0x1_0000))
+        );
+    }
+
+    #[test]
+    fn sector_misaligned() {
+        assert_eq!(UNIFORM.sector(START + 1), Err(ADDRESS_NOT_SECTOR_ALIGNED));
+        assert_eq!(
+            UNIFORM.sector(START + 0x1000),
+            Err(ADDRESS_NOT_SECTOR_ALIGNED)
+        );
+    }
+
+    #[test]
+    fn non_uniform_sectors() {
+        assert!(BOTTOM_4K.valid());
+        assert_eq!(BOTTOM_4K.min_sector_size(), 0x1000);
+        assert_eq!(BOTTOM_4K.sector(START), Ok((0, 0x1000)));
+        assert_eq!(BOTTOM_4K.sector(START + 0x1_F000), Ok((0x1_F000, 0x1000)));
+        assert_eq!(BOTTOM_4K.sector(START + 0x2_0000), Ok((0x2_0000, 0x1_0000)));
+        assert_eq!(
+            BOTTOM_4K.sector(START + 0x2_1000),
+            Err(ADDRESS_NOT_SECTOR_ALIGNED)
+        );
+        assert_eq!(
+            BOTTOM_4K.sector(START + SIZE - 0x1_0000),
+            Ok((SIZE - 0x1_0000, 0x1_0000))
+        );
+    }
+
+    #[test]
+    fn valid() {
+        assert!(UNIFORM.valid());
+
+        let cases: [&[Sector]; 4] = [
+            // No regions
+            &[],
+            // Not starting at 0
+            &[Sector {
+                size: 0x1000,
+                address: 0x1000,
+            }],
+            // Not a power of two
+            &[Sector {
+                size: 0x3000,
+                address: 0,
+            }],
+            // Not whole sectors up to the next region
+            &[
+                Sector {
+                    size: 0x1_0000,
+                    address: 0,
+                },
+                Sector {
+                    size: 0x1000,
+                    address: 0x1_8000,
+                },
+            ],
+        ];
+        for sectors in cases {
+            assert!(!Geometry { sectors, ..UNIFORM }.valid(), "{sectors:?}");
+        }
+
+        // Descending
+        let sectors = &[
+            Sector {
+                size: 0x1000,
+                address: 0,
+            },
+            Sector {
+                size: 0x1000,
+                address: 0x2000,
+            },
+            Sector {
+                size: 0x1000,
+                address: 0x1000,
+            },
+        ];
+        assert!(!Geometry { sectors, ..UNIFORM }.valid());
+    }
+
+    #[test]
+    fn page() {
+        assert_eq!(UNIFORM.page(START, 256), Ok(0));
+        assert_eq!(UNIFORM.page(START + 0x100, 10), Ok(0x100));
+        assert_eq!(UNIFORM.page(START + SIZE - 256, 256), Ok(SIZE - 256));
+    }
+
+    #[test]
+    fn page_misaligned() {
+        assert_eq!(UNIFORM.page(START + 4, 4), Err(ADDRESS_NOT_PAGE_ALIGNED));
+        assert_eq!(UNIFORM.page(START + 0x80, 0), Err(ADDRESS_NOT_PAGE_ALIGNED));
+    }
+
+    #[test]
+    fn longer_than_a_page() {
+        assert_eq!(UNIFORM.page(START, 257), Err(LENGTH_EXCEEDS_PAGE));
+        let parallel = Geometry {
+            page_size: 512,
+            ..UNIFORM
+        };
+        assert_eq!(parallel.page(START, 512), Ok(0));
+        assert_eq!(
+            parallel.page(START + 256, 256),
+            Err(ADDRESS_NOT_PAGE_ALIGNED)
+        );
+    }
+}
@@ -39,6 +39,11 @@ impl OutBuffer {
         self.space() == 0
     }
 
+    #[cfg(feature = "pipeline")]
+    fn filled(&self) -> &[u8] {
+        unsafe { self.buffer.assume_init_ref().get_unchecked(..self.len) }
+    }
+
     pub fn take<R>(&mut self, out: impl FnOnce(&[u8]) -> R) -> R {
         let data = unsafe {
             // self.len is always <= self.buffer.len()
@@ -65,11 +70,11 @@ impl From<DecompressorError> for flash_algorithm::ErrorCode {
             DecompressorError::ProgramError(status) => status,
             DecompressorError::MinizError(status) => {
                 // The lowest TINFLStatus is -4, so we add 4
-                // so the resulting error codes start at 10000.
-                10000 + (status as i8 + 4) as u32
+                // so the resulting error codes start at INFLATE_BASE.
+                crate::error::INFLATE_BASE + (status as i8 + 4) as u32
             }
-            DecompressorError::Overrun => 20000,
-            DecompressorError::Underrun => 20001,
+            DecompressorError::Overrun => crate::error::DECOMPRESSOR_OVERRUN,
+            DecompressorError::Underrun => crate::error::DECOMPRESSOR_UNDERRUN,
         };
 
         unsafe { ErrorCode::new_unchecked(code) }
@@ -81,6 +86,9 @@ pub struct Decompressor {
     output: OutBuffer,
     image_start: u32,
     offset: u32,
+    /// Number of bytes at the start of `output` that were already handed to
+    /// the flash by `pump`.
+    programmed: usize,
     remaining_compressed: usize,
 }
 
@@ -89,6 +97,7 @@ impl Decompressor {
         Self {
             image_start: 0xFFFF_FFFF,
             offset: 0,
+            programmed: 0,
             output: OutBuffer::new(),
             remaining_compressed: 0,
             decompressor: DecompressorOxide::new(),
@@ -102,6 +111,7 @@ impl Decompressor {
         self.remaining_compressed = compressed as usize;
 
         self.decompressor = DecompressorOxide::new();
+        self.programmed = 0;
         self.output.take(|_| {});
     }
 
@@ -131,8 +141,12 @@ impl Decompressor {
 
             let next_out = self.output.len;
             let out_buf = self.output.buffer();
-            let (new_status, in_bytes, out_bytes) =
-                decompress(&mut self.decompressor, &mut input, out_buf, next_out, flags);
+            // Inflate may stop early for output space, count what it consumed.
+            let (new_status, in_bytes, out_bytes) = crate::stats::timed_by(
+                crate::stats::Op::Inflate,
+                || decompress(&mut self.decompressor, &mut input, out_buf, next_out, flags),
+                |&(_, in_bytes, _)| in_bytes,
+            );
             status = new_status;
 
             assert!(
@@ -150,6 +164,17 @@ impl Decompressor {
                 // We're either finished or the decompressor can't continue
                 // until we flush the buffer.
                 self.flush()?;
+                if status == TINFLStatus::Done {
+                    // Report a rejected boot header from the image's last
+                    // page, UnInit can't.
+                    let status = crate::finish_program();
+                    if status != 0 {
+                        return Err(DecompressorError::ProgramError(status));
+                    }
+                }
+            } else {
+                #[cfg(feature = "pipeline")]
+                self.pump()?;
             }
         }
 
@@ -161,27 +186,31 @@ impl Decompressor {
     }
 
     pub fn flush(&mut self) -> DecompressorResult<()> {
+        // Skip whatever `pump` already programmed.
+        let programmed = core::mem::take(&mut self.programmed);
         let mut offset = self.offset;
-        let address = self.image_start + offset;
+        let mut address = self.image_start + offset;
 
         // Take buffer contents, write to flash and update offset.
         let status = self.output.take(|data| {
+            let data = &data[programmed..];
             offset += data.len() as u32;
-            let mut flash_addr: u32 = address - crate::MEMORY_MAP_FLEXSPI_START_ADDRESS;
             let mut status: u32 = 0;
             for page in data.chunks(crate::PAGE_SIZE as usize) {
-                status = unsafe {
-                    crate::rom_api::flexspi_nor_flash_page_program(
-                        crate::FLEXSPI_INSTANCE,
-                        crate::NOR_CONFIG.as_ptr(),
-                        flash_addr,
-                        page.as_ptr() as *const u32,
-                    )
+                status = match crate::GEOMETRY.page(address, page.len() as u32) {
+                    Ok(flash_addr) => crate::traced!(
+                        Trace,
+                        "flush_page",
+                        address,
+                        page.len(),
+                        crate::program_flash_page(flash_addr, page)
+                    ),
+                    Err(code) => code,
                 };
-                flash_addr += page.len() as u32;
                 if status != 0 {
                     break;
                 }
+                address += page.len() as u32;
             }
 
             status
@@ -196,6 +225,37 @@ impl Decompressor {
         Ok(())
     }
 
+    /// Start programming the next complete page, if the flash is idle.
+    ///
+    /// This keeps the flash busy while we go on inflating, instead of
+    /// programming everything at once when the output buffer fills up. Pages
+    /// take the same path as in `flush`, so boot header pages are held back.
+    #[cfg(feature = "pipeline")]
+    fn pump(&mut self) -> DecompressorResult<()> {
+        let programmer = unsafe { crate::pipeline::programmer() };
+        let page_size = crate::PAGE_SIZE as usize;
+
+        while self.output.len - self.programmed >= page_size {
+            if programmer.busy().map_err(DecompressorError::ProgramError)? {
+                break;
+            }
+
+            let flash_addr = crate::GEOMETRY
+                .page(self.image_start + self.offset, page_size as u32)
+                .map_err(DecompressorError::ProgramError)?;
+            let page = &self.output.filled()[self.programmed..self.programmed + page_size];
+            let status = crate::program_flash_page(flash_addr, page);
+            if status != 0 {
+                return Err(DecompressorError::ProgramError(status));
+            }
+
+            self.programmed += page_size;
+            self.offset += page_size as u32;
+        }
+
+        Ok(())
+    }
+
     pub fn program(&mut self, address: u32, mut data: &[u8]) -> DecompressorResult<()> {
         if self.image_start != address {
             // Finish previous image
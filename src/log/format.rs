@@ -0,0 +1,110 @@
+//! The backend independent part of logging: levels and the `ufmt` writer.
+//!
+//! Nothing in here touches the hardware, so the host tools build it for
+//! their tests.
+
+#![allow(dead_code)]
+
+use core::convert::Infallible;
+
+use ufmt_write::uWrite;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    /// Whether messages at this level pass the threshold `max`, where 0
+    /// turns logging off.
+    #[inline(always)]
+    pub const fn enabled_at(self, max: u8) -> bool {
+        self as u8 <= max
+    }
+
+    pub const fn prefix(self) -> &'static str {
+        match self {
+            Level::Error => "[ERROR] ",
+            Level::Warn => "[WARN] ",
+            Level::Info => "[INFO] ",
+            Level::Debug => "[DEBUG] ",
+            Level::Trace => "[TRACE] ",
+        }
+    }
+}
+
+/// Hands everything `ufmt` writes to a backend's `write`.
+pub struct Writer(pub fn(&[u8]));
+
+impl uWrite for Writer {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        (self.0)(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+
+    const LEVELS: [Level; 5] = [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    thread_local! {
+        static OUTPUT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn capture(bytes: &[u8]) {
+        OUTPUT.with(|output| output.borrow_mut().extend_from_slice(bytes));
+    }
+
+    fn take() -> String {
+        OUTPUT.with(|output| String::from_utf8(output.take()).unwrap())
+    }
+
+    #[test]
+    fn off_disables_everything() {
+        assert!(LEVELS.iter().all(|level| !level.enabled_at(0)));
+    }
+
+    #[test]
+    fn threshold_includes_more_severe_levels() {
+        // IMXRT_LOG_LEVEL=info
+        let enabled: Vec<bool> = LEVELS.iter().map(|level| level.enabled_at(3)).collect();
+        assert_eq!(enabled, [true, true, true, false, false]);
+
+        assert!(LEVELS.iter().all(|level| level.enabled_at(5)));
+    }
+
+    #[test]
+    fn prefixes() {
+        let prefixes: Vec<&str> = LEVELS.iter().map(|level| level.prefix()).collect();
+        assert_eq!(
+            prefixes,
+            ["[ERROR] ", "[WARN] ", "[INFO] ", "[DEBUG] ", "[TRACE] "]
+        );
+    }
+
+    #[test]
+    fn writer_passes_bytes_through() {
+        let mut writer = Writer(capture);
+        writer.write_str(Level::Warn.prefix()).unwrap();
+        writer.write_str("erase addr=").unwrap();
+        writer.write_char('4').unwrap();
+        writer.write_str("\n").unwrap();
+        assert_eq!(take(), "[WARN] erase addr=4\n");
+    }
+}
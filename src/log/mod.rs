@@ -0,0 +1,62 @@
+//! Debug logging for `dprintln!`.
+//!
+//! The output goes to one of these backends, picked by Cargo feature:
+//!
+//! - `log-rtt`: RTT, through the debug probe that's already attached
+//! - `log-semihosting`: the debugger's (or QEMU's) stdout via ARM semihosting
+//! - `log-itm`: ITM stimulus port 0, over SWO
+//! - otherwise: LPUART (see `uart.rs` for the instance and pads)
+//!
+//! Each backend provides `init` and `write`; [`Writer`] puts `ufmt` on top.
+//! The levels and the writer live in `format.rs`, which builds on the host.
+//!
+//! Messages are filtered by [`Level`] against `MAX_LEVEL`, which build.rs
+//! derives from `IMXRT_LOG_LEVEL` (default: `info`).
+
+mod format;
+pub use format::{Level, Writer};
+
+#[cfg(any(
+    all(feature = "log-rtt", feature = "log-semihosting"),
+    all(feature = "log-rtt", feature = "log-itm"),
+    all(feature = "log-semihosting", feature = "log-itm"),
+))]
+compile_error!("only one of `log-rtt`, `log-semihosting` and `log-itm` can be enabled");
+
+#[cfg(feature = "log-rtt")]
+mod rtt;
+#[cfg(feature = "log-rtt")]
+use rtt as backend;
+
+#[cfg(feature = "log-semihosting")]
+mod semihosting;
+#[cfg(feature = "log-semihosting")]
+use semihosting as backend;
+
+#[cfg(feature = "log-itm")]
+mod itm;
+#[cfg(feature = "log-itm")]
+use itm as backend;
+
+#[cfg(not(any(feature = "log-rtt", feature = "log-semihosting", feature = "log-itm")))]
+mod uart;
+#[cfg(not(any(feature = "log-rtt", feature = "log-semihosting", feature = "log-itm")))]
+use uart as backend;
+
+// MAX_LEVEL, generated by build.rs from IMXRT_LOG_LEVEL.
+include!(concat!(env!("OUT_DIR"), "/log_level.rs"));
+
+impl Level {
+    #[inline(always)]
+    pub const fn enabled(self) -> bool {
+        self.enabled_at(MAX_LEVEL)
+    }
+}
+
+#[allow(dead_code)]
+pub const LOG: Writer = Writer(backend::write);
+
+#[allow(dead_code)]
+pub unsafe fn init() {
+    backend::init();
+}
@@ -0,0 +1,36 @@
+//! RTT log backend.
+//!
+//! The control block (`_SEGGER_RTT`) is found by the host through the
+//! target description's `rtt_location`. The up channel never blocks: when no
+//! host is draining it, whatever doesn't fit is dropped, so logging can't
+//! stall the flash algorithm.
+
+use rtt_target::{rtt_init, UpChannel};
+
+const BUFFER_SIZE: usize = 1024;
+
+static mut CHANNEL: Option<UpChannel> = None;
+
+pub fn write(bytes: &[u8]) {
+    unsafe {
+        if let Some(channel) = CHANNEL.as_mut() {
+            channel.write(bytes);
+        }
+    }
+}
+
+pub unsafe fn init() {
+    if CHANNEL.is_none() {
+        let channels = rtt_init! {
+            up: {
+                0: {
+                    size: BUFFER_SIZE
+                    mode: NoBlockTrim
+                    name: "Terminal"
+                }
+            }
+        };
+
+        CHANNEL = Some(channels.up.0);
+    }
+}
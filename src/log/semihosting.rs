@@ -0,0 +1,23 @@
+//! ARM semihosting log backend.
+//!
+//! Works with QEMU and any probe that services semihosting requests. Without
+//! a debugger attached, the `BKPT` used for semihosting faults, so only use
+//! this when one is.
+
+use cortex_m_semihosting::hio::{self, HostStream};
+
+static mut STDOUT: Option<HostStream> = None;
+
+pub fn write(bytes: &[u8]) {
+    unsafe {
+        if let Some(stdout) = STDOUT.as_mut() {
+            let _ = stdout.write_all(bytes);
+        }
+    }
+}
+
+pub unsafe fn init() {
+    if STDOUT.is_none() {
+        STDOUT = hio::hstdout().ok();
+    }
+}
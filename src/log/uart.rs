@@ -1,4 +1,4 @@
-use core::convert::Infallible;
+//! Blocking LPUART log backend.
 
 // for serial Write trait
 use cortex_m::prelude::*;
@@ -6,43 +6,26 @@ use cortex_m::prelude::*;
 use imxrt_hal as hal;
 use imxrt_ral as ral;
 
-use ufmt_write::uWrite;
-
 pub const XTAL_OSCILLATOR_FREQUENCY: u32 = 24_000_000;
 const UART_DIVIDER: u32 = 3;
 pub const UART_FREQUENCY: u32 = XTAL_OSCILLATOR_FREQUENCY / UART_DIVIDER;
 
-// The lpuart number and TX/RX pads.
-// These particular pads correspond to the pins labeled TX and RX on the MicroMod ATP board
-const LPUART_INSTANCE: u8 = 6;
-type TxPad = hal::iomuxc::pads::gpio_ad_b0::GPIO_AD_B0_02;
-type RxPad = hal::iomuxc::pads::gpio_ad_b0::GPIO_AD_B0_03;
-
-const BAUD: u32 = 115200;
+// The lpuart number, TX/RX pads and baud rate (LPUART_INSTANCE, TxPad, RxPad, BAUD),
+// plus take_pads() and take_lpuart() to get hold of them.
+// Generated by build.rs from the IMXRT_LOG_* environment variables.
+include!(concat!(env!("OUT_DIR"), "/log_uart.rs"));
 
 static mut CONSOLE: Option<hal::lpuart::Lpuart<hal::lpuart::Pins<TxPad, RxPad>, LPUART_INSTANCE>> =
     None;
 
-pub struct UARTWriter;
-
-impl uWrite for UARTWriter {
-    type Error = Infallible;
-
-    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
-        for c in s.bytes() {
-            unsafe {
-                let _ = nb::block!(CONSOLE.as_mut().unwrap_unchecked().write(c));
-            }
+pub fn write(bytes: &[u8]) {
+    for &c in bytes {
+        unsafe {
+            let _ = nb::block!(CONSOLE.as_mut().unwrap_unchecked().write(c));
         }
-
-        Ok(())
     }
 }
 
-#[allow(dead_code)]
-pub const LOG: UARTWriter = UARTWriter;
-
-#[allow(dead_code)]
 pub unsafe fn init() {
     if CONSOLE.is_none() {
         let mut instances = ral::Instances::instances();
@@ -58,10 +41,8 @@ pub unsafe fn init() {
         hal::ccm::clock_gate::lpuart::<LPUART_INSTANCE>()
             .set(&mut instances.CCM, hal::ccm::clock_gate::ON);
 
-        let pads = hal::iomuxc::pads::Pads::new();
-        let tx: TxPad = pads.gpio_ad_b0.p02;
-        let rx: RxPad = pads.gpio_ad_b0.p03;
-        let console = lpuart::<_, _, LPUART_INSTANCE>(instances.LPUART6, tx, rx, BAUD);
+        let (tx, rx) = take_pads(hal::iomuxc::pads::Pads::new());
+        let console = lpuart::<_, _, LPUART_INSTANCE>(take_lpuart(instances), tx, rx, BAUD);
 
         CONSOLE = Some(console);
     }
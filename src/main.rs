@@ -6,13 +6,38 @@ use core::mem::MaybeUninit;
 use flash_algorithm::*;
 
 use rom_api::{flexspi_nor_config_t, serial_nor_config_option_t};
+use stats::Op;
 
 mod chip;
+mod command;
+mod die;
+mod error;
+mod fcb;
+mod geometry;
+mod page;
+mod protect;
+mod recover;
 mod rom_api;
+mod session;
+mod stats;
 
 #[cfg(feature = "miniz")]
 mod inflate;
 
+#[cfg(feature = "pipeline")]
+mod pipeline;
+
+#[cfg(feature = "checksum")]
+mod checksum;
+#[cfg(feature = "checksum")]
+mod hash;
+
+#[cfg(any(feature = "fcb", feature = "boot-check"))]
+mod boot;
+
+#[cfg(feature = "quad-program")]
+mod quad;
+
 #[cfg(feature = "log")]
 mod log;
 
@@ -40,87 +65,242 @@ macro_rules! dprintln {
 
 pub(crate) use dprintln;
 
-/// The ROM APIs only support 1 single FLASH device connected to PORTA and FLEXSPIA_SS0
+/// `dprintln!` at a `log::Level`, prefixed with the level. Levels above the
+/// build-time threshold (`IMXRT_LOG_LEVEL`, see build.rs) are compiled out.
+#[cfg(feature = "log")]
+#[allow(unused_macros)]
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)*) => {
+        if $crate::log::Level::$level.enabled() {
+            ufmt::uwrite!($crate::log::LOG, "{}", $crate::log::Level::$level.prefix()).ok();
+            $crate::dprintln!($($arg)*);
+        }
+    };
+}
+
+#[cfg(not(feature = "log"))]
+#[allow(unused_macros)]
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)*) => {};
+}
+
+#[allow(unused_macros)]
+macro_rules! log_error {
+    ($($arg:tt)*) => { $crate::log_at!(Error, $($arg)*) };
+}
+
+#[allow(unused_macros)]
+macro_rules! log_warn {
+    ($($arg:tt)*) => { $crate::log_at!(Warn, $($arg)*) };
+}
+
+#[allow(unused_macros)]
+macro_rules! log_info {
+    ($($arg:tt)*) => { $crate::log_at!(Info, $($arg)*) };
+}
+
+#[allow(unused_macros)]
+macro_rules! log_debug {
+    ($($arg:tt)*) => { $crate::log_at!(Debug, $($arg)*) };
+}
+
+#[allow(unused_macros)]
+macro_rules! log_trace {
+    ($($arg:tt)*) => { $crate::log_at!(Trace, $($arg)*) };
+}
+
+/// Evaluate `$status` (a ROM status, 0 on success) and trace the operation at
+/// `$level` with its address, length, status and the cycles it took.
+/// Failures are logged as errors, whatever `$level` is.
+#[cfg(feature = "log")]
+#[allow(unused_macros)]
+macro_rules! traced {
+    ($level:ident, $op:literal, $addr:expr, $len:expr, $status:expr) => {{
+        let start = $crate::chip::cycles();
+        let status: u32 = $status;
+        let cycles = $crate::chip::cycles().wrapping_sub(start);
+        if status == 0 {
+            $crate::log_at!(
+                $level,
+                "{} addr={} len={} status={} cycles={}",
+                $op,
+                $addr,
+                $len,
+                status,
+                cycles
+            );
+        } else {
+            $crate::log_at!(
+                Error,
+                "{} addr={} len={} status={} cycles={}",
+                $op,
+                $addr,
+                $len,
+                status,
+                cycles
+            );
+        }
+        status
+    }};
+}
+
+#[cfg(not(feature = "log"))]
+#[allow(unused_macros)]
+macro_rules! traced {
+    ($level:ident, $op:literal, $addr:expr, $len:expr, $status:expr) => {
+        $status
+    };
+}
+
+#[allow(unused_imports)]
+pub(crate) use {log_at, log_debug, log_error, log_info, log_trace, log_warn, traced};
+
+/// The ROM APIs only support FLEXSPI, with a single flash on PORTA and FLEXSPIA_SS0, or
+/// two in parallel mode on PORTA and PORTB
 const FLEXSPI_INSTANCE: u32 = 0;
 const MEMORY_MAP_FLEXSPI_START_ADDRESS: u32 = 0x60000000;
-const PAGE_SIZE: u32 = 256;
-const SECTOR_SIZE: u32 = 65536;
+// `CHIPS` and `FLASH_SIZE`, from `IMXRT_FLASH_CONNECTION` and `IMXRT_FLASH_SIZE`
+include!(concat!(env!("OUT_DIR"), "/flash.rs"));
+/// In parallel mode, each page program writes a page of both flashes.
+const PAGE_SIZE: u32 = 256 * CHIPS;
+
+// `SECTORS` and `algorithm_with_sectors!`, from `IMXRT_SECTOR_MAP`
+include!(concat!(env!("OUT_DIR"), "/sectors.rs"));
+
+const GEOMETRY: geometry::Geometry = geometry::Geometry {
+    start: MEMORY_MAP_FLEXSPI_START_ADDRESS,
+    size: FLASH_SIZE,
+    sectors: SECTORS,
+    page_size: PAGE_SIZE,
+};
+
+const _: () = assert!(GEOMETRY.valid(), "IMXRT_SECTOR_MAP doesn't fit the flash");
 
-struct Algorithm {}
+struct Algorithm {
+    function: Function,
+}
 
-algorithm!(Algorithm, {
+algorithm_with_sectors!(Algorithm, {
     device_name: "imxrt-flash-algorithm",
     device_type: DeviceType::Onchip,
     flash_address: MEMORY_MAP_FLEXSPI_START_ADDRESS,
-    flash_size: 0x00800000,
+    flash_size: FLASH_SIZE,
     page_size: PAGE_SIZE,
     empty_value: 0xFF,
     program_time_out: 2000,
     erase_time_out: 6000,
-    sectors: [{
-        size: SECTOR_SIZE,
-        address: 0x0,
-    }]
 });
 
 static mut NOR_CONFIG: MaybeUninit<flexspi_nor_config_t> = MaybeUninit::uninit();
+static mut SESSION: session::Session = session::Session::new();
+
+/// The FlexSPI clock the host asked for in `Init` (in Hz, 0 if it has no
+/// preference), as a `serialClkFreq` code. It overrides both the read clock
+/// the ROM probes the flash for and the programming clock.
+///
+/// CMSIS defines `clk` as the device's clock, and hosts pass the core clock
+/// (e.g. 600 MHz), which would pick the fastest FlexSPI clock there is. So
+/// it's only taken as a FlexSPI clock with the `init-clock` feature, for
+/// hosts that know about it.
+#[cfg(feature = "init-clock")]
+fn host_clock(clock: u32) -> Option<u8> {
+    if clock == 0 {
+        None
+    } else {
+        Some(chip::clock_code(clock))
+    }
+}
+
+#[cfg(not(feature = "init-clock"))]
+fn host_clock(_clock: u32) -> Option<u8> {
+    None
+}
 
 impl FlashAlgorithm for Algorithm {
-    fn new(_address: u32, _clock: u32, _function: Function) -> Result<Self, ErrorCode> {
+    fn new(address: u32, clock: u32, function: Function) -> Result<Self, ErrorCode> {
         unsafe {
             chip::init();
 
             #[cfg(feature = "log")]
             log::init();
 
-            dprintln!("Initializing flash algorithm...");
+            log_info!(
+                "Initializing flash algorithm (address={}, clock={}, function={})...",
+                address,
+                clock,
+                function as u32
+            );
 
-            // Flash options
-            let mut option: serial_nor_config_option_t = core::mem::zeroed();
+            if let Err(code) = GEOMETRY.range(address, 0) {
+                log_error!("Address {} is outside of the FlexSPI window", address);
+                return Err(ErrorCode::new_unchecked(code));
+            }
 
-            // Set required tag
-            option.option0.B.set_tag(0x0C);
+            let clock = host_clock(clock);
+
+            // Verifying only reads, which doesn't need the programming clock.
+            let key = session::Key {
+                // QuadSPI NOR - Quad SDR Read (100MHz) (what the NXP flashloader uses)
+                max_freq: clock.unwrap_or(6),
+                program_clock: match function {
+                    Function::Verify => None,
+                    _ => Some(clock.unwrap_or(chip::PROGRAM_CLOCK)),
+                },
+            };
+
+            stats::set_core_clock(chip::core_clock());
+
+            match SESSION.begin(key) {
+                session::Action::Reuse => log_info!("Flash already configured, skipping probe"),
+                session::Action::Probe => {
+                    let mut status = configure(key);
+                    if status == 0 {
+                        status = check_geometry();
+                    }
+                    SESSION.finish(status == 0);
+                    if status != 0 {
+                        return Err(ErrorCode::new_unchecked(status as _));
+                    }
+                }
+            }
 
-            // from serialClkFreq docs under "Table 9-15. FlexSPI Configuration block"
-            // 1 – 30 MHz
-            // 2 – 50 MHz
-            // 3 – 60 MHz
-            // 4 – 75 MHz
-            // 5 – 80 MHz
-            // 6 – 100 MHz
-            // 7 – 120 MHz
-            // 8 – 133 MHz
-            // 9 - 166 MHz
-            // Other value: 30 MH
+            if !matches!(function, Function::Verify) {
+                let status = protect::unlock();
+                if status != 0 {
+                    return Err(ErrorCode::new_unchecked(status));
+                }
+            }
 
-            // QuadSPI NOR - Quad SDR Read (100MHz) (what the NXP flashloader uses)
-            option.option0.B.set_max_freq(6);
+            Ok(Self { function })
+        }
+    }
 
-            // Get NOR configuration block
-            let status =
-                rom_api::flexspi_nor_get_config(FLEXSPI_INSTANCE, NOR_CONFIG.as_mut_ptr(), &option);
+    fn erase_all(&mut self) -> Result<(), ErrorCode> {
+        wait_for_program()?;
+
+        // A chip erase only erases the selected die.
+        let dies = unsafe { die::count() };
+        let die_size = FLASH_SIZE / dies;
+        let mut status = 0;
+        for die in 0..dies {
+            status = unsafe { die::select(die * die_size) };
             if status != 0 {
-                return Err(ErrorCode::new_unchecked(status as _));
+                break;
             }
-
-            // By default, the ROM API will always use a 30 MHz clock for programming.
-            // If we trust the Flash to keep up, we could change the frequency here:
-            // (*NOR_CONFIG.as_mut_ptr()).ipcmdSerialClkFreq = 6;
-
-            // initialize flash
-            let status = rom_api::flexspi_nor_flash_init(FLEXSPI_INSTANCE, NOR_CONFIG.as_ptr());
+            status = traced!(
+                Debug,
+                "erase_all",
+                MEMORY_MAP_FLEXSPI_START_ADDRESS + die * die_size,
+                die_size,
+                stats::timed(Op::Erase, die_size as usize, || unsafe {
+                    rom_api::flexspi_nor_flash_erase_all(FLEXSPI_INSTANCE, NOR_CONFIG.as_ptr())
+                })
+            );
             if status != 0 {
-                return Err(ErrorCode::new_unchecked(status as _));
+                break;
             }
-            Ok(Self {})
         }
-    }
-
-    fn erase_all(&mut self) -> Result<(), ErrorCode> {
-        // dprintln!("Erase All");
-
-        let status =
-            unsafe { rom_api::flexspi_nor_flash_erase_all(FLEXSPI_INSTANCE, NOR_CONFIG.as_ptr()) };
+        invalidate(MEMORY_MAP_FLEXSPI_START_ADDRESS, FLASH_SIZE);
         if status != 0 {
             return Err(unsafe { ErrorCode::new_unchecked(status as _) });
         }
@@ -129,17 +309,32 @@ impl FlashAlgorithm for Algorithm {
     }
 
     fn erase_sector(&mut self, addr: u32) -> Result<(), ErrorCode> {
-        // dprintln!("Erase sector addr:{}", addr);
-
-        let flash_addr: u32 = addr - MEMORY_MAP_FLEXSPI_START_ADDRESS;
-        let status = unsafe {
-            rom_api::flexspi_nor_flash_erase(
-                FLEXSPI_INSTANCE,
-                NOR_CONFIG.as_ptr(),
-                flash_addr,
-                SECTOR_SIZE,
-            )
-        };
+        let (flash_addr, size) = GEOMETRY
+            .sector(addr)
+            .map_err(|code| unsafe { ErrorCode::new_unchecked(code) })?;
+
+        wait_for_program()?;
+
+        let status = unsafe { die::select(flash_addr) };
+        if status != 0 {
+            return Err(unsafe { ErrorCode::new_unchecked(status as _) });
+        }
+
+        let status = traced!(
+            Debug,
+            "erase_sector",
+            addr,
+            size,
+            stats::timed(Op::Erase, size as usize, || unsafe {
+                rom_api::flexspi_nor_flash_erase(
+                    FLEXSPI_INSTANCE,
+                    NOR_CONFIG.as_ptr(),
+                    flash_addr,
+                    size,
+                )
+            })
+        );
+        invalidate(addr, size);
 
         if status != 0 {
             return Err(unsafe { ErrorCode::new_unchecked(status as _) });
@@ -150,39 +345,462 @@ impl FlashAlgorithm for Algorithm {
 
     #[cfg(feature = "miniz")]
     fn program_page(&mut self, addr: u32, data: &[u8]) -> Result<(), ErrorCode> {
-        // dprintln!("Program Page addr:{} size:{}", addr, data.len());
+        log_trace!("program_page addr={} len={}", addr, data.len());
+        // `addr` is where the image starts, the decompressor checks where
+        // its pages end up.
+        GEOMETRY
+            .page(addr, 0)
+            .map_err(|code| unsafe { ErrorCode::new_unchecked(code) })?;
         unsafe { inflate::decompressor() }.program(addr, data)?;
         Ok(())
     }
 
     #[cfg(not(feature = "miniz"))]
     fn program_page(&mut self, addr: u32, data: &[u8]) -> Result<(), ErrorCode> {
-        // dprintln!("Program Page addr:{} size:{}", addr, data.len());
-        let flash_addr: u32 = addr - crate::MEMORY_MAP_FLEXSPI_START_ADDRESS;
-        let status = unsafe {
-            crate::rom_api::flexspi_nor_flash_page_program(
-                crate::FLEXSPI_INSTANCE,
-                crate::NOR_CONFIG.as_ptr(),
-                flash_addr,
-                data.as_ptr() as *const u32,
-            )
-        };
+        let flash_addr = GEOMETRY
+            .page(addr, data.len() as u32)
+            .map_err(|code| unsafe { ErrorCode::new_unchecked(code) })?;
+        let status = traced!(
+            Trace,
+            "program_page",
+            addr,
+            data.len(),
+            program_flash_page(flash_addr, data)
+        );
+
         if status != 0 {
             return Err(unsafe { ErrorCode::new_unchecked(status as _) });
         }
         Ok(())
     }
+
+    /// Compare `size` bytes of flash at `addr` with `data`, one die at a
+    /// time: the debugger's own memory-mapped reads only see the selected
+    /// die. Fails with the first address that differs.
+    #[cfg(feature = "stacked-die")]
+    fn verify(&mut self, addr: u32, size: u32, data: Option<&[u8]>) -> Result<(), ErrorCode> {
+        // Nothing to compare against
+        let data = match data {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+
+        let mut offset = 0;
+        let mut mismatch = None;
+        let read = unsafe {
+            die::for_each_slice(addr, size, |flash| {
+                let expected = data.get(offset..).unwrap_or(&[]);
+                if mismatch.is_none() {
+                    mismatch = flash
+                        .iter()
+                        .zip(expected)
+                        .position(|(actual, expected)| actual != expected)
+                        .map(|index| addr + (offset + index) as u32);
+                }
+                offset += flash.len();
+            })
+        };
+        read.map_err(|code| unsafe { ErrorCode::new_unchecked(code) })?;
+
+        match mismatch {
+            Some(address) => {
+                log_error!("Verify failed at {}", address);
+                Err(unsafe { ErrorCode::new_unchecked(address) })
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Probe the flash for `key.max_freq` into `NOR_CONFIG` and initialize the
+/// ROM driver.
+///
+/// With the `fcb` feature, a valid FCB at the start of the flash is used
+/// instead, including its read clock.
+unsafe fn configure(key: session::Key) -> u32 {
+    let program_clock = key.program_clock.unwrap_or(chip::SAFE_CLOCK);
+
+    // Wake the flash up from whatever mode the firmware left it in
+    let status = traced!(
+        Info,
+        "recover",
+        MEMORY_MAP_FLEXSPI_START_ADDRESS,
+        0u32,
+        recover::recover()
+    );
+    if status != 0 {
+        return status;
+    }
+
+    #[cfg(feature = "fcb")]
+    {
+        if let Some(config) = boot::load_fcb() {
+            log_info!("Using the FCB from flash");
+            NOR_CONFIG.write(config);
+            return init_flash(program_clock);
+        }
+    }
+
+    // Flash options
+    let mut option: serial_nor_config_option_t = core::mem::zeroed();
+
+    // Set required tag
+    option.option0.B.set_tag(0x0C);
+
+    if CHIPS == 2 {
+        // Two flashes on A1 and B1, in parallel mode
+        option.option0.B.set_option_size(1);
+        option.option1.B.set_flash_connection(1);
+    }
+
+    // from serialClkFreq docs under "Table 9-15. FlexSPI Configuration block"
+    // 1 – 30 MHz
+    // 2 – 50 MHz
+    // 3 – 60 MHz
+    // 4 – 75 MHz
+    // 5 – 80 MHz
+    // 6 – 100 MHz
+    // 7 – 120 MHz
+    // 8 – 133 MHz
+    // 9 - 166 MHz
+    // Other value: 30 MH
+    option.option0.B.set_max_freq(key.max_freq as u32);
+
+    // Get NOR configuration block
+    let status = traced!(
+        Info,
+        "get_config",
+        MEMORY_MAP_FLEXSPI_START_ADDRESS,
+        0u32,
+        stats::timed(Op::Init, 0, || rom_api::flexspi_nor_get_config(
+            FLEXSPI_INSTANCE,
+            NOR_CONFIG.as_mut_ptr(),
+            &option
+        ))
+    );
+    if status != 0 {
+        return status;
+    }
+
+    init_flash(program_clock)
+}
+
+/// Check that the ROM driver can erase the smallest sector of the sector
+/// map, and program `PAGE_SIZE` bytes at a time. It erases in units of
+/// `sectorSize`, from SFDP or the FCB, which for parts without 4 KB erase (or
+/// with `isUniformBlockSize`) is the block size. In parallel mode, both sizes
+/// cover the two flashes.
+unsafe fn check_geometry() -> u32 {
+    let config = &*NOR_CONFIG.as_ptr();
+
+    if config.pageSize < PAGE_SIZE {
+        log_error!(
+            "Flash programs {} bytes at a time, expected {}",
+            config.pageSize,
+            PAGE_SIZE
+        );
+        return error::PAGE_SIZE_UNSUPPORTED;
+    }
+    let erase_size = config.sectorSize;
+    let min_sector_size = GEOMETRY.min_sector_size();
+
+    if erase_size == 0 || erase_size > min_sector_size {
+        log_error!(
+            "Flash erases {} bytes at a time, but the sector map has {} byte sectors",
+            erase_size,
+            min_sector_size
+        );
+        return error::SECTOR_MAP_UNSUPPORTED;
+    }
+
+    0
+}
+
+/// Number of bytes `init_flash` reads back to check the programming clock.
+const READ_BACK_SIZE: usize = 128;
+
+/// Initialize the ROM driver for erasing and programming at `clock` (a
+/// `serialClkFreq` code).
+///
+/// By default, the ROM API always uses a 30 MHz clock for IP commands. For
+/// anything faster, the start of the flash is read at 30 MHz, then the ROM
+/// driver and the FlexSPI root clock are switched to `clock` and it's read
+/// again. If the two reads differ, or the fast one fails, everything goes
+/// back to 30 MHz.
+unsafe fn init_flash(clock: u8) -> u32 {
+    let config = &mut *NOR_CONFIG.as_mut_ptr();
+
+    if FLASH_SIZE / CHIPS > fcb::ADDRESS_3BYTE_LIMIT {
+        let status = four_byte_addresses(config);
+        if status != 0 {
+            return status;
+        }
+    }
+
+    #[cfg(feature = "stacked-die")]
+    {
+        let status = die::detect(config);
+        if status != 0 {
+            return status;
+        }
+    }
+
+    #[cfg(feature = "quad-program")]
+    {
+        let status = quad::enable(config);
+        if status != 0 {
+            return status;
+        }
+    }
+
+    config.ipcmdSerialClkFreq = chip::SAFE_CLOCK;
+    let status = rom_init();
+    if status != 0 || clock == chip::SAFE_CLOCK {
+        return status;
+    }
+
+    let mut expected = [0u32; READ_BACK_SIZE / 4];
+    let status = read_back(&mut expected);
+    if status != 0 {
+        return status;
+    }
+
+    config.ipcmdSerialClkFreq = clock;
+    let mut status = rom_init();
+    if status == 0 {
+        chip::set_flexspi_clock(clock);
+
+        let mut actual = [0u32; READ_BACK_SIZE / 4];
+        status = read_back(&mut actual);
+        if status == 0 && actual == expected {
+            log_info!("Programming clock: {}", clock);
+            return 0;
+        }
+    }
+
+    log_warn!(
+        "Read-back at programming clock {} failed (status={}), falling back to 30 MHz",
+        clock,
+        status
+    );
+    chip::set_flexspi_clock(chip::SAFE_CLOCK);
+    config.ipcmdSerialClkFreq = chip::SAFE_CLOCK;
+    rom_init()
+}
+
+/// Switch the read, erase and program sequences to 4-byte addresses. The
+/// ROM's probe builds them with 3-byte addresses, which only reach the first
+/// 16 MB; an FCB may already use 4-byte ones, which are left as they are.
+fn four_byte_addresses(config: &mut flexspi_nor_config_t) -> u32 {
+    let lut = &mut config.memConfig.lookupTable;
+    for index in fcb::seq::ADDRESSED {
+        let mut seq = fcb::lut_sequence(lut, index);
+        if let Err(_command) = fcb::four_byte_addresses(&mut seq) {
+            log_error!(
+                "Command {} (LUT sequence {}) has no 4-byte address form",
+                _command,
+                index
+            );
+            return error::FOUR_BYTE_ADDRESS_UNSUPPORTED;
+        }
+        fcb::set_lut_sequence(lut, index, &seq);
+    }
+
+    log_info!("Using 4-byte addresses");
+    0
+}
+
+unsafe fn rom_init() -> u32 {
+    traced!(
+        Info,
+        "init",
+        MEMORY_MAP_FLEXSPI_START_ADDRESS,
+        0u32,
+        stats::timed(Op::Init, 0, || rom_api::flexspi_nor_flash_init(
+            FLEXSPI_INSTANCE,
+            NOR_CONFIG.as_ptr()
+        ))
+    )
+}
+
+/// Read the start of the flash through the ROM driver, at the IP command
+/// clock.
+unsafe fn read_back(buffer: &mut [u32]) -> u32 {
+    rom_api::flexspi_nor_flash_read(
+        FLEXSPI_INSTANCE,
+        NOR_CONFIG.as_ptr(),
+        buffer.as_mut_ptr(),
+        0,
+        (buffer.len() * 4) as u32,
+    )
+}
+
+static mut PAGE_BUFFER: page::PageBuffer<{ PAGE_SIZE as usize }> = page::PageBuffer::new();
+
+/// Copy `page` into the staging buffer and pad it with 0xFF to a full page,
+/// see `page.rs`. The buffer is reused by the next call.
+fn stage_page(page: &[u8]) -> &'static [u8] {
+    unsafe { PAGE_BUFFER.stage(page) }
+}
+
+/// Program one page at `flash_addr` (an offset into the flash).
+///
+/// With the pipeline, this returns as soon as the page is on its way, the
+/// next call (or uninit) waits for the flash to become ready again.
+#[cfg(feature = "pipeline")]
+fn write_page(flash_addr: u32, page: &[u8]) -> u32 {
+    stats::timed(Op::Program, page.len(), || {
+        match unsafe { pipeline::programmer() }.program(flash_addr, page) {
+            Ok(()) => 0,
+            Err(status) => status,
+        }
+    })
+}
+
+#[cfg(not(feature = "pipeline"))]
+fn write_page(flash_addr: u32, page: &[u8]) -> u32 {
+    let status = unsafe { die::select(flash_addr) };
+    if status != 0 {
+        return status;
+    }
+
+    let len = page.len();
+    let page = stage_page(page);
+    let status = stats::timed(Op::Program, len, || unsafe {
+        rom_api::flexspi_nor_flash_page_program(
+            FLEXSPI_INSTANCE,
+            NOR_CONFIG.as_ptr(),
+            flash_addr,
+            page.as_ptr() as *const u32,
+        )
+    });
+    invalidate(MEMORY_MAP_FLEXSPI_START_ADDRESS + flash_addr, PAGE_SIZE);
+    status
+}
+
+/// `write_page`, reading back the first page programmed with quad page
+/// program.
+#[cfg(feature = "quad-program")]
+fn checked_write_page(flash_addr: u32, page: &[u8]) -> u32 {
+    unsafe { quad::program(flash_addr, page, write_page) }
+}
+
+#[cfg(not(feature = "quad-program"))]
+fn checked_write_page(flash_addr: u32, page: &[u8]) -> u32 {
+    write_page(flash_addr, page)
+}
+
+/// `checked_write_page`, holding back the boot header until it has been
+/// checked.
+#[cfg(feature = "boot-check")]
+fn program_flash_page(flash_addr: u32, page: &[u8]) -> u32 {
+    unsafe { boot::checker() }.program(flash_addr, page, checked_write_page)
+}
+
+#[cfg(not(feature = "boot-check"))]
+fn program_flash_page(flash_addr: u32, page: &[u8]) -> u32 {
+    checked_write_page(flash_addr, page)
+}
+
+/// Check and program a boot header that is still held back, at the end of
+/// an image.
+#[cfg(feature = "boot-check")]
+fn finish_program() -> u32 {
+    traced!(
+        Info,
+        "boot_header",
+        MEMORY_MAP_FLEXSPI_START_ADDRESS,
+        boot::HEADER_SIZE,
+        unsafe { boot::checker() }.finish(checked_write_page)
+    )
+}
+
+#[cfg(not(feature = "boot-check"))]
+fn finish_program() -> u32 {
+    0
+}
+
+/// Finish the boot header at UnInit. Complete images were already finished
+/// by their last page; this only covers images the host didn't send to the
+/// end, and UnInit can't report errors, so they're only logged.
+fn end_program() {
+    let _ = finish_program();
+    #[cfg(feature = "boot-check")]
+    unsafe { boot::checker() }.reset();
+}
+
+/// Restore the block protection `new` cleared.
+#[cfg(feature = "relock")]
+fn relock() {
+    let _ = traced!(
+        Info,
+        "relock",
+        MEMORY_MAP_FLEXSPI_START_ADDRESS,
+        FLASH_SIZE,
+        unsafe { protect::relock() }
+    );
+}
+
+#[cfg(not(feature = "relock"))]
+fn relock() {}
+
+/// Make memory-mapped reads of `len` bytes at `addr` see what's in the flash
+/// now: clear the FlexSPI AHB buffers and drop stale D-cache lines.
+///
+/// Pipelined page programs are still in flight when they return, the
+/// pipeline invalidates each page once the flash is done with it.
+fn invalidate(addr: u32, len: u32) {
+    unsafe {
+        rom_api::flexspi_clear_cache(FLEXSPI_INSTANCE);
+        chip::invalidate_dcache(addr, len);
+    }
+}
+
+/// Wait for a page program started by the pipeline to finish.
+#[cfg(feature = "pipeline")]
+fn wait_for_program() -> Result<(), ErrorCode> {
+    unsafe { pipeline::programmer() }
+        .wait()
+        .map_err(|status| unsafe { ErrorCode::new_unchecked(status as _) })
+}
+
+#[cfg(not(feature = "pipeline"))]
+fn wait_for_program() -> Result<(), ErrorCode> {
+    Ok(())
 }
 
 impl Drop for Algorithm {
     #[cfg(feature = "miniz")]
     fn drop(&mut self) {
-        // TODO: don't swallow possible error
-        let _ = unsafe { inflate::decompressor() }.flush();
+        log_info!("Uninitializing flash algorithm");
+        // Erase and verify sessions never set up the decompressor.
+        // A complete stream was flushed by its last page, this is only left
+        // to do for one the host didn't send to the end.
+        if matches!(self.function, Function::Program) {
+            if let Err(_err) = unsafe { inflate::decompressor() }.flush() {
+                log_error!(
+                    "Programming the rest of the image failed: {}",
+                    ErrorCode::from(_err).get()
+                );
+            }
+        }
+        end_program();
+        let _ = wait_for_program();
+        relock();
+        // Leave memory-mapped reads on the first die of a stacked-die part.
+        let _ = unsafe { die::select(0) };
+        invalidate(MEMORY_MAP_FLEXSPI_START_ADDRESS, FLASH_SIZE);
+        stats::dump();
     }
 
     #[cfg(not(feature = "miniz"))]
     fn drop(&mut self) {
-        // no-op
+        log_info!("Uninitializing flash algorithm");
+        end_program();
+        let _ = wait_for_program();
+        relock();
+        let _ = unsafe { die::select(0) };
+        invalidate(MEMORY_MAP_FLEXSPI_START_ADDRESS, FLASH_SIZE);
+        stats::dump();
     }
 }
@@ -0,0 +1,97 @@
+//! Staging pages for the ROM.
+//!
+//! The ROM always programs a full page and reads it with word loads, so
+//! neither a short final chunk nor an unaligned host buffer can be passed on
+//! as is.
+//!
+//! This module doesn't depend on anything else in the crate, so the staging
+//! can be tested on the host.
+
+#![allow(dead_code)]
+
+/// Word aligned staging buffer for a page of `N` bytes.
+#[repr(C, align(4))]
+pub struct PageBuffer<const N: usize>([u8; N]);
+
+impl<const N: usize> PageBuffer<N> {
+    pub const fn new() -> Self {
+        Self([0; N])
+    }
+
+    /// Copy `page` into the buffer and pad it with 0xFF to a full page.
+    /// Anything past the page is cut off.
+    pub fn stage(&mut self, page: &[u8]) -> &[u8] {
+        let len = page.len().min(N);
+        self.0[..len].copy_from_slice(&page[..len]);
+        self.0[len..].fill(0xFF);
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_SIZE: usize = 256;
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn full_page() {
+        let mut buffer = PageBuffer::<PAGE_SIZE>::new();
+        assert_eq!(buffer.stage(&data(PAGE_SIZE)), data(PAGE_SIZE));
+    }
+
+    #[test]
+    fn short_page_is_padded() {
+        let mut buffer = PageBuffer::<PAGE_SIZE>::new();
+        let staged = buffer.stage(&data(10));
+        assert_eq!(staged.len(), PAGE_SIZE);
+        assert_eq!(staged[..10], data(10));
+        assert!(staged[10..].iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn empty_page_is_erased() {
+        let mut buffer = PageBuffer::<PAGE_SIZE>::new();
+        assert!(buffer.stage(&[]).iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn long_page_is_cut_off() {
+        let mut buffer = PageBuffer::<PAGE_SIZE>::new();
+        assert_eq!(buffer.stage(&data(PAGE_SIZE + 3)), data(PAGE_SIZE));
+    }
+
+    #[test]
+    fn nothing_left_from_the_previous_page() {
+        let mut buffer = PageBuffer::<PAGE_SIZE>::new();
+        buffer.stage(&[0; PAGE_SIZE]);
+        let staged = buffer.stage(&data(100));
+        assert_eq!(staged[..100], data(100));
+        assert!(staged[100..].iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn word_aligned_from_unaligned_source() {
+        let source = data(PAGE_SIZE + 3);
+        let mut buffer = PageBuffer::<PAGE_SIZE>::new();
+        for offset in 1..4 {
+            let page = &source[offset..offset + PAGE_SIZE];
+            let staged = buffer.stage(page);
+            assert_eq!(staged.as_ptr() as usize % 4, 0);
+            assert_eq!(staged, page);
+        }
+    }
+
+    #[test]
+    fn parallel_mode_page() {
+        let mut buffer = PageBuffer::<{ 2 * PAGE_SIZE }>::new();
+        let staged = buffer.stage(&data(PAGE_SIZE + 1));
+        assert_eq!(staged.len(), 2 * PAGE_SIZE);
+        assert_eq!(staged[..PAGE_SIZE + 1], data(PAGE_SIZE + 1));
+        assert!(staged[PAGE_SIZE + 1..].iter().all(|&byte| byte == 0xFF));
+    }
+}
@@ -0,0 +1,149 @@
+//! Non-blocking page programming.
+//!
+//! `flexspi_nor_flash_page_program` spins on the flash's busy bit before it
+//! returns, so nothing else can happen while a page is being written. Here the
+//! write enable and page program LUT sequences are issued directly through
+//! `flexspi_command_xfer`, and the busy bit is polled separately. That lets the
+//! decompressor (or the host, while it transfers the next chunk) make progress
+//! while the flash is busy.
+//!
+//! There's one output buffer, not two alternating ones. The FlexSPI IP TX
+//! FIFO is smaller than a page, but `flexspi_command_xfer` keeps refilling it
+//! and only returns once the whole page has been shifted out to the flash.
+//! From then on, the page being programmed lives in the flash's own page
+//! buffer, which is the second buffer: the source can be reused as soon as
+//! `start` returns, and the next page is inflated into it while the flash
+//! is busy.
+//!
+//! Memory-mapped reads of a page are only invalidated once the flash reports
+//! it done, see [`PageProgrammer::busy`].
+
+use crate::fcb::seq;
+use crate::rom_api::{self, flexspi_xfer_t, spi_status_t};
+
+static mut PROGRAMMER: PageProgrammer = PageProgrammer::new();
+
+pub unsafe fn programmer() -> &'static mut PageProgrammer {
+    &mut PROGRAMMER
+}
+
+pub struct PageProgrammer {
+    /// Where the page program sequence was issued, while the flash hasn't
+    /// reported ready yet.
+    in_flight: Option<u32>,
+}
+
+impl PageProgrammer {
+    pub const fn new() -> Self {
+        Self { in_flight: None }
+    }
+
+    /// Returns `true` while the previously started page is still being
+    /// programmed. Once it's done, memory-mapped reads see the new data.
+    pub fn busy(&mut self) -> Result<bool, spi_status_t> {
+        let flash_addr = match self.in_flight {
+            Some(flash_addr) => flash_addr,
+            None => return Ok(false),
+        };
+
+        let mut status: u32 = 0;
+        let mut read_status = xfer(
+            rom_api::_FlexSPIOperationType_kFlexSpiOperation_Read,
+            0,
+            seq::READSTATUS,
+        );
+        // In parallel mode, the status bytes of the two flashes are
+        // interleaved, A first.
+        read_status.rxBuffer = &mut status;
+        read_status.rxSize = crate::CHIPS;
+        check(unsafe { rom_api::flexspi_command_xfer(crate::FLEXSPI_INSTANCE, &mut read_status) })?;
+
+        let config = unsafe { &(*crate::NOR_CONFIG.as_ptr()).memConfig };
+        let busy = (0..crate::CHIPS).any(|chip| {
+            let busy_bit = (status >> (8 * chip + config.busyOffset as u32)) & 1;
+            // busyBitPolarity: 0 - the busy flag is 1 while the flash is busy
+            if config.busyBitPolarity == 0 {
+                busy_bit == 1
+            } else {
+                busy_bit == 0
+            }
+        });
+
+        if !busy {
+            self.in_flight = None;
+            crate::invalidate(
+                crate::MEMORY_MAP_FLEXSPI_START_ADDRESS + flash_addr,
+                crate::PAGE_SIZE,
+            );
+        }
+        Ok(busy)
+    }
+
+    /// Block until the previously started page (if any) has been programmed.
+    pub fn wait(&mut self) -> Result<(), spi_status_t> {
+        while self.busy()? {}
+        Ok(())
+    }
+
+    /// Start programming `page` at `flash_addr` without waiting for the flash
+    /// to finish. The caller must make sure the flash isn't busy.
+    pub fn start(&mut self, flash_addr: u32, page: &[u8]) -> Result<(), spi_status_t> {
+        check(unsafe { crate::die::select(flash_addr) })?;
+
+        let mut write_enable = xfer(
+            rom_api::_FlexSPIOperationType_kFlexSpiOperation_Command,
+            flash_addr,
+            seq::WRITEENABLE,
+        );
+        check(unsafe {
+            rom_api::flexspi_command_xfer(crate::FLEXSPI_INSTANCE, &mut write_enable)
+        })?;
+
+        // The TX FIFO is filled word by word, before the xfer returns.
+        let page = crate::stage_page(page);
+        let mut page_program = xfer(
+            rom_api::_FlexSPIOperationType_kFlexSpiOperation_Write,
+            flash_addr,
+            seq::PAGEPROGRAM,
+        );
+        page_program.txBuffer = page.as_ptr() as *mut u32;
+        page_program.txSize = page.len() as u32;
+        check(unsafe {
+            rom_api::flexspi_command_xfer(crate::FLEXSPI_INSTANCE, &mut page_program)
+        })?;
+
+        self.in_flight = Some(flash_addr);
+        Ok(())
+    }
+
+    /// Wait for the flash to become ready, then start programming `page`.
+    pub fn program(&mut self, flash_addr: u32, page: &[u8]) -> Result<(), spi_status_t> {
+        self.wait()?;
+        self.start(flash_addr, page)
+    }
+}
+
+fn xfer(
+    operation: rom_api::flexspi_operation_t,
+    base_address: u32,
+    seq_id: usize,
+) -> flexspi_xfer_t {
+    flexspi_xfer_t {
+        operation,
+        baseAddress: base_address,
+        seqId: seq_id as u32,
+        seqNum: 1,
+        isParallelModeEnable: crate::CHIPS == 2,
+        txBuffer: core::ptr::null_mut(),
+        txSize: 0,
+        rxBuffer: core::ptr::null_mut(),
+        rxSize: 0,
+    }
+}
+
+fn check(status: spi_status_t) -> Result<(), spi_status_t> {
+    if status != 0 {
+        return Err(status);
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Block protection.
+//!
+//! Many NOR flashes ship with (or are left with) block protect bits set in
+//! their status register, and the ROM then fails erases and page programs
+//! without a hint why. Before erasing or programming, the algorithm reads
+//! the status register of each flash (and die) and clears BP0 - BP3 if any
+//! are set. Winbond and Macronix parts also get a Global Block Unlock (98h),
+//! which clears the volatile individual block locks.
+//!
+//! With the `relock` feature, the status registers are written back as they
+//! were at uninit. Individual block locks aren't restored: they're volatile,
+//! and come back at the next power-up on parts configured to use them.
+//!
+//! On parts where Write Status Register (01h) also writes a second register
+//! (Winbond and GigaDevice status register 2, Macronix configuration
+//! register), that register is read first and written back unchanged, so
+//! the Quad Enable bit survives.
+
+use crate::command;
+use crate::error::PROTECTION_LOCKED;
+
+/// BP0 - BP3 in status register 1.
+const BP_MASK: u32 = 0x3C;
+/// Write In Progress in status register 1.
+const WIP: u32 = 0x01;
+
+const READ_ID: u8 = 0x9F;
+const READ_STATUS: u8 = 0x05;
+const WRITE_STATUS: u8 = 0x01;
+const WRITE_ENABLE: u8 = 0x06;
+const GLOBAL_BLOCK_UNLOCK: u8 = 0x98;
+
+/// Status registers of each die, as they were before the first unlock.
+#[cfg(feature = "relock")]
+static mut SAVED: [Option<command::Values>; 2] = [None; 2];
+
+/// Read command of the register Write Status Register writes after status
+/// register 1, by JEDEC manufacturer ID.
+fn second_register(manufacturer: u8) -> Option<u8> {
+    match manufacturer {
+        // Winbond, GigaDevice: status register 2
+        0xEF | 0xC8 => Some(0x35),
+        // Macronix: configuration register
+        0xC2 => Some(0x15),
+        _ => None,
+    }
+}
+
+/// Whether the part understands Global Block Unlock.
+fn has_block_locks(manufacturer: u8) -> bool {
+    matches!(manufacturer, 0xEF | 0xC2)
+}
+
+/// Clear block protection on every flash and die.
+pub unsafe fn unlock() -> u32 {
+    match for_each_die(|manufacturer, die| unlock_die(manufacturer, die)) {
+        Ok(()) => 0,
+        Err(status) => status,
+    }
+}
+
+/// Write the status registers saved by the first [`unlock`] back.
+#[cfg(feature = "relock")]
+pub unsafe fn relock() -> u32 {
+    let status = for_each_die(|manufacturer, die| {
+        let saved = match SAVED[die as usize].take() {
+            Some(saved) => saved,
+            None => return Ok(()),
+        };
+        if registers(manufacturer)? != saved {
+            crate::log_info!("Restoring block protection (status {})", saved[0]);
+            write_registers(manufacturer, saved)?;
+        }
+        Ok(())
+    });
+    match status {
+        Ok(()) => 0,
+        Err(status) => status,
+    }
+}
+
+/// Run `f` with the JEDEC manufacturer ID and each die selected in turn,
+/// then select the first die again.
+unsafe fn for_each_die(mut f: impl FnMut(u8, u32) -> Result<(), u32>) -> Result<(), u32> {
+    let manufacturer = command::read(READ_ID, 1)?[0] as u8;
+
+    let dies = crate::die::count();
+    for die in 0..dies {
+        check(crate::die::select(die * (crate::FLASH_SIZE / dies)))?;
+        f(manufacturer, die)?;
+    }
+    check(crate::die::select(0))
+}
+
+unsafe fn unlock_die(manufacturer: u8, _die: u32) -> Result<(), u32> {
+    let registers = registers(manufacturer)?;
+    #[cfg(feature = "relock")]
+    if SAVED[_die as usize].is_none() {
+        SAVED[_die as usize] = Some(registers);
+    }
+
+    if registers.iter().any(|value| value & BP_MASK != 0) {
+        crate::log_warn!("Clearing block protection (status {})", registers[0]);
+        write_registers(manufacturer, registers.map(|value| value & !BP_MASK))?;
+
+        let status = command::read(READ_STATUS, 1)?;
+        if status.iter().any(|value| value & BP_MASK != 0) {
+            crate::log_error!("Block protection is still set (status {})", status[0]);
+            return Err(PROTECTION_LOCKED);
+        }
+    }
+
+    if has_block_locks(manufacturer) {
+        check(command::command(WRITE_ENABLE))?;
+        check(command::command(GLOBAL_BLOCK_UNLOCK))?;
+        wait_ready()?;
+    }
+
+    Ok(())
+}
+
+/// Status register 1 in the low byte, the [`second_register`] (if any) in
+/// the high byte.
+unsafe fn registers(manufacturer: u8) -> Result<command::Values, u32> {
+    let mut values = command::read(READ_STATUS, 1)?;
+    if let Some(cmd) = second_register(manufacturer) {
+        let second = command::read(cmd, 1)?;
+        for (value, second) in values.iter_mut().zip(second) {
+            *value |= second << 8;
+        }
+    }
+    Ok(values)
+}
+
+unsafe fn write_registers(manufacturer: u8, values: command::Values) -> Result<(), u32> {
+    let len = if second_register(manufacturer).is_some() {
+        2
+    } else {
+        1
+    };
+    check(command::command(WRITE_ENABLE))?;
+    check(command::write(WRITE_STATUS, values, len))?;
+    wait_ready()
+}
+
+/// Wait for a status register write to finish.
+unsafe fn wait_ready() -> Result<(), u32> {
+    while command::read(READ_STATUS, 1)?
+        .iter()
+        .any(|value| value & WIP != 0)
+    {}
+    Ok(())
+}
+
+fn check(status: u32) -> Result<(), u32> {
+    if status != 0 {
+        return Err(status);
+    }
+    Ok(())
+}
@@ -0,0 +1,187 @@
+//! Quad page program.
+//!
+//! The ROM's probe programs with Page Program (02h), one data pad at a time.
+//! With the `quad-program` feature, a flash that's already read on four pads
+//! (so its Quad Enable bit is set and IO2/IO3 are wired up) gets the quad
+//! page program command of its manufacturer instead, which moves the page
+//! data four times as fast.
+//!
+//! The first page programmed that way that clears any bits is read back
+//! (an all-0xFF page reads back right whether the program worked or not,
+//! so it doesn't count). If it doesn't match,
+//! the page program sequence goes back to the probed one for as long as the
+//! algorithm stays loaded. The page is programmed again if the quad program
+//! left none of its bits cleared that should be set, and otherwise fails with
+//! `QUAD_PROGRAM_FAILED`, so the host erases it again.
+
+use crate::command;
+use crate::fcb::{self, seq, Instr, Opcode, Pads, Sequence};
+use crate::rom_api::{self, flexspi_nor_config_t};
+
+/// Quad page program command (3-byte address) and address pads, by JEDEC
+/// manufacturer ID.
+fn quad_program(manufacturer: u8) -> Option<(u8, Pads)> {
+    match manufacturer {
+        // Winbond, GigaDevice, ISSI, Micron: Quad Input Page Program
+        0xEF | 0xC8 | 0x9D | 0x20 => Some((0x32, Pads::Single)),
+        // Macronix: 4PP, address on four pads too
+        0xC2 => Some((0x38, Pads::Quad)),
+        _ => None,
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum State {
+    /// Programming with the probed sequence.
+    Off,
+    /// Programming with the quad sequence, no page with a 0 bit read back
+    /// yet.
+    Unchecked,
+    /// The quad sequence programmed a page correctly.
+    Checked,
+    /// The quad sequence programmed a page wrong, don't use it again.
+    Failed,
+}
+
+struct Quad {
+    state: State,
+    /// The probed page program sequence.
+    probed: Sequence,
+}
+
+static mut QUAD: Quad = Quad {
+    state: State::Off,
+    probed: [Instr::STOP; fcb::INSTRUCTIONS],
+};
+
+/// Switch the page program sequence in `config` to quad page program, if
+/// the flash supports it. Called with FlexSPI up, before the ROM driver is
+/// initialized from `config`.
+pub unsafe fn enable(config: &mut flexspi_nor_config_t) -> u32 {
+    if QUAD.state == State::Failed {
+        return 0;
+    }
+    QUAD.state = State::Off;
+
+    let lut = &mut config.memConfig.lookupTable;
+    let reads_quad = fcb::lut_sequence(lut, seq::READ).iter().any(|instr| {
+        matches!(instr.opcode(), Some(Opcode::ReadSdr | Opcode::ReadDdr))
+            && instr.pads() == Pads::Quad
+    });
+    if !reads_quad {
+        return 0;
+    }
+
+    // Only replace the plain single pad sequence the ROM's probe builds.
+    let probed = fcb::lut_sequence(lut, seq::PAGEPROGRAM);
+    let (four_byte, data) = match probed {
+        [cmd, raddr, write, ..]
+            if cmd.opcode() == Some(Opcode::CmdSdr)
+                && raddr.opcode() == Some(Opcode::RaddrSdr)
+                && write.opcode() == Some(Opcode::WriteSdr)
+                && [cmd, raddr, write]
+                    .iter()
+                    .all(|instr| instr.pads() == Pads::Single)
+                && probed[3..].iter().all(|&instr| instr == Instr::STOP) =>
+        {
+            match (cmd.operand(), raddr.operand()) {
+                (0x02, 24) => (false, write.operand()),
+                (0x12, 32) => (true, write.operand()),
+                _ => return 0,
+            }
+        }
+        _ => return 0,
+    };
+
+    let manufacturer = match command::read(0x9F, 1) {
+        Ok(id) => id[0] as u8,
+        Err(status) => return status,
+    };
+    let (cmd, address_pads) = match quad_program(manufacturer) {
+        Some(quad) => quad,
+        None => return 0,
+    };
+
+    let mut quad = fcb::sequence(&[
+        Instr::new(Opcode::CmdSdr, Pads::Single, cmd),
+        Instr::new(Opcode::RaddrSdr, address_pads, 24),
+        Instr::new(Opcode::WriteSdr, Pads::Quad, data),
+    ]);
+    if four_byte {
+        // Both commands have a 4-byte form.
+        let _ = fcb::four_byte_addresses(&mut quad);
+    }
+
+    crate::log_info!("Quad page program: {}", quad[0].operand());
+    fcb::set_lut_sequence(lut, seq::PAGEPROGRAM, &quad);
+    QUAD = Quad {
+        state: State::Unchecked,
+        probed,
+    };
+    0
+}
+
+/// Program `page` at `flash_addr` with `write`, and read the first quad
+/// programmed page with a 0 bit back.
+pub unsafe fn program(flash_addr: u32, page: &[u8], write: fn(u32, &[u8]) -> u32) -> u32 {
+    let status = write(flash_addr, page);
+    if status != 0 || QUAD.state != State::Unchecked {
+        return status;
+    }
+    // Staging pads the page with 0xFF.
+    if page.iter().all(|&byte| byte == 0xFF) {
+        return 0;
+    }
+
+    if let Err(status) = crate::wait_for_program() {
+        return status.get();
+    }
+    let mut actual = [0u32; crate::PAGE_SIZE as usize / 4];
+    let status = rom_api::flexspi_nor_flash_read(
+        crate::FLEXSPI_INSTANCE,
+        crate::NOR_CONFIG.as_ptr(),
+        actual.as_mut_ptr(),
+        flash_addr,
+        crate::PAGE_SIZE,
+    );
+    if status != 0 {
+        return status;
+    }
+
+    let expected = crate::stage_page(page)
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+    if expected.clone().eq(actual) {
+        crate::log_info!("Quad page program checked");
+        QUAD.state = State::Checked;
+        return 0;
+    }
+
+    let probed = QUAD.probed;
+    crate::log_warn!(
+        "Quad page program at {} read back wrong, falling back to {}",
+        flash_addr,
+        probed[0].operand()
+    );
+    QUAD.state = State::Failed;
+    let lut = &mut (*crate::NOR_CONFIG.as_mut_ptr()).memConfig.lookupTable;
+    fcb::set_lut_sequence(lut, seq::PAGEPROGRAM, &probed);
+    let status = rom_api::flexspi_update_lut(
+        crate::FLEXSPI_INSTANCE,
+        seq::PAGEPROGRAM as u32,
+        fcb::lut_sequence_ptr(lut, seq::PAGEPROGRAM),
+        1,
+    );
+    if status != 0 {
+        return status;
+    }
+
+    // Programming only clears bits.
+    if expected
+        .zip(actual)
+        .any(|(expected, actual)| expected & !actual != 0)
+    {
+        return crate::error::QUAD_PROGRAM_FAILED;
+    }
+    write(flash_addr, page)
+}
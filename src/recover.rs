@@ -0,0 +1,141 @@
+//! Flash recovery.
+//!
+//! Firmware running from the flash can leave it in a state the ROM's SFDP
+//! probe doesn't talk to: in deep power-down, in QPI mode, or in continuous
+//! read (XIP enhanced) mode, where the next transaction starts at the
+//! address. `flexspi_nor_get_config` then fails with
+//! `kSPI_Status_FLEXSPINOR_Flash_NotFound` (20109).
+//!
+//! Before probing, the algorithm brings FlexSPI up with a minimal single pad
+//! configuration and sends, at every pad width a mode could be listening
+//! on: Release from Deep Power-Down (ABh), Exit QPI (FFh, F5h), a run of
+//! all-ones to leave continuous read mode, and Software Reset (66h, 99h).
+//! Commands a flash doesn't understand in its current mode are ignored.
+
+use crate::command;
+use crate::fcb::{self, DeviceType, Instr, Opcode, Pads, ReadSampleClock, SerialClock};
+use crate::rom_api;
+
+const RELEASE_POWER_DOWN: u8 = 0xAB;
+const EXIT_QPI: [u8; 2] = [0xFF, 0xF5];
+const RESET_ENABLE: u8 = 0x66;
+const RESET: u8 = 0x99;
+
+/// controllerMiscOption bit that enables parallel mode.
+const PARALLEL_MODE: u32 = 2;
+
+/// tRES1 is 3 µs on most parts, up to 30 µs on some.
+const POWER_UP_US: u32 = 50;
+/// tRST is 30 µs after a reset during a read, up to 12 ms during an erase;
+/// the busy poll of the following operations covers the latter.
+const RESET_US: u32 = 100;
+
+/// Send the recovery commands.
+pub unsafe fn recover() -> u32 {
+    let status = init();
+    if status != 0 {
+        return status;
+    }
+
+    use Pads::*;
+    for pads in [Single, Dual, Quad] {
+        let status = send(pads, &[RELEASE_POWER_DOWN]);
+        if status != 0 {
+            return status;
+        }
+    }
+    delay_us(POWER_UP_US);
+
+    for cmd in EXIT_QPI {
+        let status = send(Quad, &[cmd]);
+        if status != 0 {
+            return status;
+        }
+    }
+
+    // Mode bits other than the continuous read value end the mode: 16 clocks
+    // of ones covers 3- and 4-byte addresses, on any number of pads.
+    for (pads, bytes) in [(Quad, 8), (Dual, 4), (Single, 2)] {
+        let status = send(pads, &[0xFF; 8][..bytes]);
+        if status != 0 {
+            return status;
+        }
+    }
+
+    for pads in [Quad, Dual, Single] {
+        for cmd in [RESET_ENABLE, RESET] {
+            let status = send(pads, &[cmd]);
+            if status != 0 {
+                return status;
+            }
+        }
+    }
+    delay_us(RESET_US);
+
+    0
+}
+
+/// Configure FlexSPI for the flash at 30 MHz, reading with Read (03h).
+///
+/// The configuration goes straight into `NOR_CONFIG`, which the probe
+/// overwrites next: an [`fcb::Builder`] and its serialized copy would take
+/// over 1 KB of the algorithm's stack.
+unsafe fn init() -> u32 {
+    use Opcode::*;
+    use Pads::*;
+
+    let config = crate::NOR_CONFIG.as_mut_ptr();
+    config.write_bytes(0, 1);
+    let config = &mut *config;
+    let size = crate::FLASH_SIZE / crate::CHIPS;
+
+    let mem = &mut config.memConfig;
+    mem.tag = fcb::TAG;
+    mem.version = fcb::VERSION;
+    mem.readSampleClkSrc = ReadSampleClock::LoopbackInternally as u8;
+    mem.csHoldTime = 3;
+    mem.csSetupTime = 3;
+    mem.deviceType = DeviceType::SerialNor as u8;
+    mem.sflashPadType = Single as u8;
+    mem.serialClkFreq = SerialClock::Mhz30 as u8;
+    mem.sflashA1Size = size;
+    if crate::CHIPS == 2 {
+        // A1 and B1, in parallel mode
+        mem.sflashB1Size = size;
+        mem.controllerMiscOption |= 1 << PARALLEL_MODE;
+    }
+    fcb::set_lut_sequence(
+        &mut mem.lookupTable,
+        fcb::seq::READ,
+        &fcb::sequence(&[
+            Instr::new(CmdSdr, Single, 0x03),
+            Instr::new(RaddrSdr, Single, 24),
+            Instr::new(ReadSdr, Single, 4),
+        ]),
+    );
+    fcb::set_lut_sequence(
+        &mut mem.lookupTable,
+        fcb::seq::READSTATUS,
+        &fcb::sequence(&[
+            Instr::new(CmdSdr, Single, 0x05),
+            Instr::new(ReadSdr, Single, 4),
+        ]),
+    );
+    config.pageSize = 256;
+    config.sectorSize = 4 * 1024;
+
+    rom_api::flexspi_nor_flash_init(crate::FLEXSPI_INSTANCE, config)
+}
+
+/// Send `bytes` as commands on `pads`, with chip select held.
+unsafe fn send(pads: Pads, bytes: &[u8]) -> u32 {
+    let mut instrs = [Instr::STOP; fcb::INSTRUCTIONS];
+    for (instr, &byte) in instrs.iter_mut().zip(bytes) {
+        *instr = Instr::new(Opcode::CmdSdr, pads, byte);
+    }
+    command::send(&instrs)
+}
+
+fn delay_us(us: u32) {
+    cortex_m::asm::delay(crate::chip::core_clock() / 1_000_000 * us);
+}
@@ -0,0 +1,182 @@
+//! `IMXRT_SECTOR_MAP` parsing.
+//!
+//! This module doesn't depend on anything else in the crate. `build.rs` pulls
+//! it in to generate `SECTORS`, and xtask to check that an algorithm's
+//! `FlashDevice` has the sectors its profile asks for.
+
+#![allow(dead_code)]
+
+/// Where a sector region starts, in bytes of one flash.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Offset {
+    Start(u32),
+    /// Counted back from the end of the flash.
+    End(u32),
+}
+
+/// Common sector layouts, as `(size, offset)` regions.
+pub const SECTOR_MAPS: &[(&str, &[(u32, Offset)])] = &[
+    ("uniform-64k", &[(0x10000, Offset::Start(0))]),
+    ("uniform-4k", &[(0x1000, Offset::Start(0))]),
+    // 4 KB parameter sectors in the first 64 KB block, e.g. S25FL-L / S25FS-S
+    (
+        "bottom-4k",
+        &[
+            (0x1000, Offset::Start(0)),
+            (0x10000, Offset::Start(0x10000)),
+        ],
+    ),
+    // 4 KB parameter sectors in the last 64 KB block
+    (
+        "top-4k",
+        &[(0x10000, Offset::Start(0)), (0x1000, Offset::End(0x10000))],
+    ),
+];
+
+/// The map used when `IMXRT_SECTOR_MAP` isn't set.
+pub const DEFAULT: &str = "uniform-64k";
+
+/// A sector map, one of [`SECTOR_MAPS`] or a list of `SIZE@OFFSET` regions,
+/// as `(size, offset)` regions. Each region runs up to the next one, the
+/// last one to the end of the flash.
+pub fn parse(value: &str) -> Result<Vec<(u32, Offset)>, &'static str> {
+    let value = value.trim();
+
+    if let Some((_, regions)) = SECTOR_MAPS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+    {
+        return Ok(regions.to_vec());
+    }
+
+    let mut regions: Vec<(u32, u32)> = Vec::new();
+    for region in value.split(',') {
+        let (size, offset) = region.split_once('@').ok_or("not a known layout")?;
+        let size = size_value(size).ok_or("bad sector size")?;
+        let offset = size_value(offset).ok_or("bad offset")?;
+
+        if !size.is_power_of_two() || size < 0x1000 {
+            return Err("sector sizes are powers of two, at least 4K");
+        }
+        if offset & (size - 1) != 0 {
+            return Err("each region has to start on a sector boundary");
+        }
+        match regions.last() {
+            None if offset != 0 => return Err("the first region has to start at 0"),
+            Some(&(_, last_offset)) if offset <= last_offset => {
+                return Err("regions have to be in ascending order")
+            }
+            Some(&(last_size, last_offset)) if (offset - last_offset) & (last_size - 1) != 0 => {
+                return Err("each region has to end on a sector boundary")
+            }
+            _ => {}
+        }
+        regions.push((size, offset));
+    }
+
+    Ok(regions
+        .into_iter()
+        .map(|(size, offset)| (size, Offset::Start(offset)))
+        .collect())
+}
+
+/// A size or offset: decimal, `0x` hex, or with a `K` / `M` suffix.
+pub fn size_value(value: &str) -> Option<u32> {
+    let value = value.trim().to_ascii_uppercase();
+    let (digits, scale) = match value.as_bytes().last()? {
+        b'K' => (&value[..value.len() - 1], 1 << 10),
+        b'M' => (&value[..value.len() - 1], 1 << 20),
+        _ => (&value[..], 1),
+    };
+    let number = match digits.strip_prefix("0X") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    number.checked_mul(scale)
+}
+
+/// `(size, address)` of each region, as the algorithm's `FlashDevice` lists
+/// them: scaled by `chips`, addresses relative to the start of a flash of
+/// `flash_size` bytes (all chips together).
+pub fn resolve(regions: &[(u32, Offset)], flash_size: u32, chips: u32) -> Vec<(u32, u32)> {
+    regions
+        .iter()
+        .map(|&(size, offset)| {
+            let address = match offset {
+                Offset::Start(offset) => offset * chips,
+                Offset::End(offset) => flash_size - offset * chips,
+            };
+            (size * chips, address)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_values() {
+        assert_eq!(size_value("4096"), Some(0x1000));
+        assert_eq!(size_value("0x20000"), Some(0x2_0000));
+        assert_eq!(size_value(" 4k "), Some(0x1000));
+        assert_eq!(size_value("32M"), Some(0x200_0000));
+        assert_eq!(size_value("0x10K"), Some(0x4000));
+        assert_eq!(size_value("8192M"), None);
+        assert_eq!(size_value("K"), None);
+        assert_eq!(size_value(""), None);
+    }
+
+    #[test]
+    fn named_layouts() {
+        assert_eq!(parse(DEFAULT), Ok(vec![(0x10000, Offset::Start(0))]));
+        let top = parse("TOP-4K").unwrap();
+        assert_eq!(
+            resolve(&top, 0x80_0000, 1),
+            [(0x10000, 0), (0x1000, 0x7F_0000)]
+        );
+        assert_eq!(
+            resolve(&top, 0x100_0000, 2),
+            [(0x20000, 0), (0x2000, 0xFE_0000)]
+        );
+    }
+
+    #[test]
+    fn region_lists() {
+        let regions = parse("4K@0, 64K@0x20000").unwrap();
+        assert_eq!(
+            regions,
+            [
+                (0x1000, Offset::Start(0)),
+                (0x10000, Offset::Start(0x2_0000))
+            ]
+        );
+        assert_eq!(
+            resolve(&regions, 0x200_0000, 1),
+            [(0x1000, 0), (0x10000, 0x2_0000)]
+        );
+    }
+
+    #[test]
+    fn invalid_maps() {
+        for (value, reason) in [
+            ("uniform-8k", "not a known layout"),
+            ("4X@0", "bad sector size"),
+            ("4K@zero", "bad offset"),
+            ("3K@0", "sector sizes are powers of two, at least 4K"),
+            ("2K@0", "sector sizes are powers of two, at least 4K"),
+            (
+                "64K@0x1000",
+                "each region has to start on a sector boundary",
+            ),
+            ("4K@0x1000", "the first region has to start at 0"),
+            ("4K@0,64K@0", "regions have to be in ascending order"),
+            (
+                "64K@0,4K@0x1000",
+                "each region has to end on a sector boundary",
+            ),
+        ] {
+            assert_eq!(parse(value), Err(reason), "{value}");
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Init / UnInit bookkeeping across the phases of one loaded session.
+//!
+//! probe-rs calls Init separately for erasing, programming and verifying,
+//! while the algorithm stays loaded in RAM. Probing the flash and
+//! initializing the ROM driver is only needed the first time, or when an
+//! Init asks for different clocks than the configuration we already have.
+//!
+//! | State                          | Event                | Next state          |
+//! |--------------------------------|----------------------|---------------------|
+//! | `Configured(k)`, `k` serves it | `begin`: `Reuse`     | `Configured(k)`     |
+//! | any other                      | `begin`: `Probe`     | `Probing(key)`      |
+//! | `Probing(key)`                 | `finish(true)`       | `Configured(key)`   |
+//! | any other                      | `finish(_)`          | `Unconfigured`      |
+//!
+//! UnInit doesn't change the state, so the configuration survives until the
+//! algorithm is reloaded (which zeroes this state again).
+//!
+//! This module doesn't depend on anything else in the crate, so the state
+//! machine can be exercised on the host.
+
+#![allow(dead_code)]
+
+/// The clocks an Init asks for, as `serialClkFreq` codes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Key {
+    /// Read clock the ROM probes the flash for.
+    pub max_freq: u8,
+    /// Clock for erasing and programming, `None` if the session doesn't
+    /// erase or program (Verify).
+    pub program_clock: Option<u8>,
+}
+
+impl Key {
+    /// Whether a configuration set up for `self` serves `request`.
+    pub fn serves(&self, request: &Key) -> bool {
+        self.max_freq == request.max_freq
+            && match request.program_clock {
+                None => true,
+                Some(clock) => self.program_clock == Some(clock),
+            }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum State {
+    /// Nothing probed yet, or the last probe failed.
+    Unconfigured,
+    /// Probing the flash and initializing the ROM driver for `Key`.
+    Probing(Key),
+    /// `NOR_CONFIG` is valid and the ROM driver initialized for `Key`.
+    Configured(Key),
+}
+
+/// What Init has to do.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    /// Probe the flash and initialize the ROM driver, then call
+    /// [`Session::finish`].
+    Probe,
+    /// The current configuration can be used as is.
+    Reuse,
+}
+
+pub struct Session {
+    state: State,
+}
+
+impl Session {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Unconfigured,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Start an Init asking for `key`.
+    pub fn begin(&mut self, key: Key) -> Action {
+        match self.state {
+            State::Configured(configured) if configured.serves(&key) => Action::Reuse,
+            _ => {
+                self.state = State::Probing(key);
+                Action::Probe
+            }
+        }
+    }
+
+    /// End the probe started by [`Session::begin`].
+    pub fn finish(&mut self, ok: bool) {
+        self.state = match self.state {
+            State::Probing(key) if ok => State::Configured(key),
+            _ => State::Unconfigured,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: Key = Key {
+        max_freq: 6,
+        program_clock: Some(1),
+    };
+    const VERIFY: Key = Key {
+        max_freq: 6,
+        program_clock: None,
+    };
+
+    fn configured(key: Key) -> Session {
+        let mut session = Session::new();
+        assert_eq!(session.begin(key), Action::Probe);
+        session.finish(true);
+        session
+    }
+
+    #[test]
+    fn first_init_probes() {
+        let mut session = Session::new();
+        assert_eq!(session.state(), State::Unconfigured);
+        assert_eq!(session.begin(PROGRAM), Action::Probe);
+        assert_eq!(session.state(), State::Probing(PROGRAM));
+        session.finish(true);
+        assert_eq!(session.state(), State::Configured(PROGRAM));
+    }
+
+    #[test]
+    fn failed_probe_is_retried() {
+        let mut session = Session::new();
+        session.begin(PROGRAM);
+        session.finish(false);
+        assert_eq!(session.state(), State::Unconfigured);
+        assert_eq!(session.begin(PROGRAM), Action::Probe);
+    }
+
+    #[test]
+    fn same_key_reuses() {
+        let mut session = configured(PROGRAM);
+        assert_eq!(session.begin(PROGRAM), Action::Reuse);
+        assert_eq!(session.state(), State::Configured(PROGRAM));
+    }
+
+    #[test]
+    fn verify_reuses_a_program_configuration() {
+        let mut session = configured(PROGRAM);
+        assert_eq!(session.begin(VERIFY), Action::Reuse);
+        assert_eq!(session.state(), State::Configured(PROGRAM));
+    }
+
+    #[test]
+    fn program_after_verify_probes() {
+        let mut session = configured(VERIFY);
+        assert_eq!(session.begin(PROGRAM), Action::Probe);
+        assert_eq!(session.state(), State::Probing(PROGRAM));
+    }
+
+    #[test]
+    fn other_clocks_probe() {
+        let mut session = configured(PROGRAM);
+        let faster_program = Key {
+            program_clock: Some(6),
+            ..PROGRAM
+        };
+        assert_eq!(session.begin(faster_program), Action::Probe);
+
+        let mut session = configured(PROGRAM);
+        let faster_read = Key {
+            max_freq: 7,
+            ..PROGRAM
+        };
+        assert_eq!(session.begin(faster_read), Action::Probe);
+    }
+
+    #[test]
+    fn failed_reprobe_drops_the_old_configuration() {
+        let mut session = configured(PROGRAM);
+        session.begin(Key {
+            max_freq: 7,
+            ..PROGRAM
+        });
+        session.finish(false);
+        assert_eq!(session.state(), State::Unconfigured);
+        assert_eq!(session.begin(PROGRAM), Action::Probe);
+    }
+
+    #[test]
+    fn finish_without_begin() {
+        let mut session = Session::new();
+        session.finish(true);
+        assert_eq!(session.state(), State::Unconfigured);
+
+        // A second finish doesn't configure anything either.
+        let mut session = configured(PROGRAM);
+        session.finish(true);
+        assert_eq!(session.state(), State::Unconfigured);
+        assert_eq!(session.begin(PROGRAM), Action::Probe);
+    }
+
+    #[test]
+    fn begin_twice_probes_for_the_last_key() {
+        let mut session = Session::new();
+        session.begin(PROGRAM);
+        assert_eq!(session.begin(VERIFY), Action::Probe);
+        session.finish(true);
+        assert_eq!(session.state(), State::Configured(VERIFY));
+    }
+}
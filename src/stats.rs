@@ -0,0 +1,130 @@
+//! Where does the flashing time go?
+//!
+//! With the `stats` feature, every ROM init, erase, page program and inflate
+//! call is timed with the DWT cycle counter and accumulated in `FLASH_STATS`.
+//! The host can read that symbol after the session, and with `log` the
+//! totals are also printed on uninit.
+//!
+//! Without the feature, [`timed`] just runs the operation.
+
+#[derive(Copy, Clone)]
+pub enum Op {
+    /// Probing the flash and initializing the ROM driver.
+    Init,
+    Erase,
+    Program,
+    Inflate,
+}
+
+#[cfg(feature = "stats")]
+#[repr(C)]
+pub struct Counter {
+    /// Number of calls.
+    pub count: u32,
+    /// Bytes erased or programmed, compressed bytes inflate consumed.
+    pub bytes: u32,
+    /// Core clock cycles spent.
+    pub cycles: u64,
+}
+
+#[cfg(feature = "stats")]
+#[repr(C)]
+pub struct Stats {
+    /// Core clock frequency the cycles were counted at.
+    pub core_clock: u32,
+    pub init: Counter,
+    pub erase: Counter,
+    pub program: Counter,
+    pub inflate: Counter,
+}
+
+#[cfg(feature = "stats")]
+impl Counter {
+    const fn new() -> Self {
+        Self {
+            count: 0,
+            bytes: 0,
+            cycles: 0,
+        }
+    }
+}
+
+#[cfg(feature = "stats")]
+#[no_mangle]
+#[used]
+pub static mut FLASH_STATS: Stats = Stats {
+    core_clock: 0,
+    init: Counter::new(),
+    erase: Counter::new(),
+    program: Counter::new(),
+    inflate: Counter::new(),
+};
+
+/// Run `f`, accounting its cycles and `bytes` to `op`.
+#[inline(always)]
+pub fn timed<R>(op: Op, bytes: usize, f: impl FnOnce() -> R) -> R {
+    timed_by(op, f, |_| bytes)
+}
+
+/// Run `f`, accounting its cycles to `op`, and the bytes `bytes` counts in
+/// its result, for operations that don't know up front how much they'll do.
+#[cfg(feature = "stats")]
+#[inline(always)]
+pub fn timed_by<R>(op: Op, f: impl FnOnce() -> R, bytes: impl FnOnce(&R) -> usize) -> R {
+    let start = crate::chip::cycles();
+    let result = f();
+    let cycles = crate::chip::cycles().wrapping_sub(start);
+
+    let counter = unsafe {
+        match op {
+            Op::Init => &mut FLASH_STATS.init,
+            Op::Erase => &mut FLASH_STATS.erase,
+            Op::Program => &mut FLASH_STATS.program,
+            Op::Inflate => &mut FLASH_STATS.inflate,
+        }
+    };
+    counter.count += 1;
+    counter.bytes = counter.bytes.wrapping_add(bytes(&result) as u32);
+    counter.cycles += cycles as u64;
+
+    result
+}
+
+#[cfg(not(feature = "stats"))]
+#[inline(always)]
+pub fn timed_by<R>(_op: Op, f: impl FnOnce() -> R, _bytes: impl FnOnce(&R) -> usize) -> R {
+    f()
+}
+
+/// Record the core clock, so the host can turn cycles into time.
+#[cfg(feature = "stats")]
+pub fn set_core_clock(hz: u32) {
+    unsafe { FLASH_STATS.core_clock = hz };
+}
+
+#[cfg(not(feature = "stats"))]
+pub fn set_core_clock(_hz: u32) {}
+
+/// Print the totals through the log backend.
+#[cfg(all(feature = "stats", feature = "log"))]
+pub fn dump() {
+    let stats = unsafe { &FLASH_STATS };
+    crate::log_info!("stats: core_clock={}", stats.core_clock);
+    for (name, counter) in [
+        ("init", &stats.init),
+        ("erase", &stats.erase),
+        ("program", &stats.program),
+        ("inflate", &stats.inflate),
+    ] {
+        crate::log_info!(
+            "stats: {} count={} bytes={} cycles={}",
+            name,
+            counter.count,
+            counter.bytes,
+            counter.cycles
+        );
+    }
+}
+
+#[cfg(not(all(feature = "stats", feature = "log")))]
+pub fn dump() {}
@@ -0,0 +1,63 @@
+@ A stand-in for the algorithm's ELF, for the snapshot tests: the CMSIS
+@ entry points, a data section and the `FlashDevice` the profile's build
+@ produces. `FLASH_SIZE` and, for the S25FL256S profile, `S25FL256S` are
+@ set with `--defsym`. Rebuild with:
+@
+@   llvm-mc -triple=thumbv7em-none-eabihf -filetype=obj --defsym=FLASH_SIZE=0x800000 \
+@       algorithm.s -o algorithm.o
+@   rust-lld -flavor gnu --nmagic --entry=Init -T link.x algorithm.o -o MIMXRT1060.elf
+@
+@   llvm-mc -triple=thumbv7em-none-eabihf -filetype=obj --defsym=FLASH_SIZE=0x2000000 \
+@       --defsym=S25FL256S=1 algorithm.s -o algorithm.o
+@   rust-lld -flavor gnu --nmagic --entry=Init -T link.x algorithm.o -o MIMXRT1060-S25FL256S.elf
+
+    .syntax unified
+    .thumb
+
+    .macro entry name
+    .global \name
+    .type \name, %function
+    .thumb_func
+\name:
+    ldr r1, =state
+    str r0, [r1]
+    movs r0, #0
+    bx lr
+    .ltorg
+    .endm
+
+    .text
+    entry Init
+    entry UnInit
+    entry EraseChip
+    entry EraseSector
+    entry ProgramPage
+
+    .data
+state:
+    .word 0
+
+    .section DevDscr, "a"
+    .global FlashDevice
+    .type FlashDevice, %object
+FlashDevice:
+    .short 1                            @ version
+name:
+    .asciz "imxrt-flash-algorithm"
+    .space 128 - (. - name)
+    .short 1                            @ DeviceType::Onchip
+    .word 0x60000000                    @ flash_address
+    .word FLASH_SIZE                    @ flash_size
+    .word 0x100                         @ page_size
+    .word 0                             @ reserved
+    .byte 0xff                          @ empty_value
+    .space 3
+    .word 2000                          @ program_time_out
+    .word 6000                          @ erase_time_out
+    .ifdef S25FL256S
+    .word 0x1000, 0x0
+    .word 0x10000, 0x20000
+    .else
+    .word 0x10000, 0x0
+    .endif
+    .word 0xffffffff, 0xffffffff
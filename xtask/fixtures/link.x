@@ -0,0 +1,8 @@
+/* Lays the fixture out like flash-algorithm's memory.x: code at 0, then
+   data, then the FlashDevice description. */
+SECTIONS {
+    . = 0;
+    PrgCode : { KEEP(*(.text .text.*)) *(.rodata .rodata.*) }
+    PrgData : { *(.data .data.*) *(.bss .bss.*) }
+    DevDscr : { KEEP(*(DevDscr)) }
+}
@@ -0,0 +1,125 @@
+//! Extract the flash algorithm from the built ELF, like `target-gen elf`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use object::{Object, ObjectSection, ObjectSymbol};
+
+pub struct FlashAlgorithm {
+    pub instructions: Vec<u8>,
+    pub pc_init: u32,
+    pub pc_uninit: u32,
+    pub pc_program_page: u32,
+    pub pc_erase_sector: u32,
+    pub pc_erase_all: Option<u32>,
+    /// Only with the `stacked-die` feature.
+    pub pc_verify: Option<u32>,
+    pub data_section_offset: u32,
+    pub rtt_location: Option<u32>,
+    pub flash: FlashDevice,
+}
+
+/// The CMSIS `FlashDevice` description emitted by `flash_algorithm::algorithm!`.
+pub struct FlashDevice {
+    pub name: String,
+    pub address: u32,
+    pub size: u32,
+    pub page_size: u32,
+    pub empty: u8,
+    pub program_time_out: u32,
+    pub erase_time_out: u32,
+    /// `(size, address)` of each sector region, address relative to the
+    /// start of the flash.
+    pub sectors: Vec<(u32, u32)>,
+}
+
+pub fn read(elf: &[u8]) -> Result<FlashAlgorithm> {
+    let file = object::File::parse(elf).context("failed to parse ELF")?;
+
+    let code = file
+        .section_by_name("PrgCode")
+        .ok_or_else(|| anyhow!("missing PrgCode section"))?;
+    let code_start = code.address() as u32;
+
+    let mut instructions = code.data()?.to_vec();
+    let mut data_section_offset = 0;
+    if let Some(data) = file.section_by_name("PrgData") {
+        if data.size() > 0 {
+            data_section_offset = data.address() as u32 - code_start;
+            instructions.resize(data_section_offset as usize, 0);
+            instructions.extend_from_slice(data.data()?);
+        }
+    }
+
+    let symbol = |name: &str| -> Option<u64> {
+        file.symbols()
+            .find(|symbol| symbol.name() == Ok(name))
+            .map(|symbol| symbol.address())
+    };
+    let function = |name: &str| -> Result<u32> {
+        symbol(name)
+            .map(|address| address as u32 - code_start)
+            .ok_or_else(|| anyhow!("missing `{name}` function"))
+    };
+
+    let device = symbol("FlashDevice").ok_or_else(|| anyhow!("missing FlashDevice"))?;
+    let device_section = file
+        .sections()
+        .find(|section| (section.address()..section.address() + section.size()).contains(&device))
+        .ok_or_else(|| anyhow!("FlashDevice isn't in any section"))?;
+    let device_data = device_section.data()?;
+    let flash = flash_device(&device_data[(device - device_section.address()) as usize..])?;
+
+    Ok(FlashAlgorithm {
+        instructions,
+        pc_init: function("Init")?,
+        pc_uninit: function("UnInit")?,
+        pc_program_page: function("ProgramPage")?,
+        pc_erase_sector: function("EraseSector")?,
+        pc_erase_all: function("EraseChip").ok(),
+        pc_verify: function("Verify").ok(),
+        data_section_offset,
+        rtt_location: symbol("_SEGGER_RTT").map(|address| address as u32),
+        flash,
+    })
+}
+
+/// Decode the `#[repr(C)]` CMSIS `FlashDevice` struct.
+fn flash_device(data: &[u8]) -> Result<FlashDevice> {
+    let u32_at = |offset: usize| -> Result<u32> {
+        let bytes = data
+            .get(offset..offset + 4)
+            .ok_or_else(|| anyhow!("FlashDevice truncated"))?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+
+    let name = data
+        .get(2..130)
+        .ok_or_else(|| anyhow!("FlashDevice truncated"))?;
+    let name_len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    let name = String::from_utf8_lossy(&name[..name_len]).into_owned();
+
+    let mut sectors = Vec::new();
+    let mut offset = 160;
+    loop {
+        let size = u32_at(offset)?;
+        let address = u32_at(offset + 4)?;
+        if size == 0xFFFF_FFFF && address == 0xFFFF_FFFF {
+            break;
+        }
+        sectors.push((size, address));
+        offset += 8;
+    }
+    if sectors.is_empty() {
+        bail!("FlashDevice has no sectors");
+    }
+
+    Ok(FlashDevice {
+        name,
+        address: u32_at(132)?,
+        size: u32_at(136)?,
+        page_size: u32_at(140)?,
+        empty: data[148],
+        program_time_out: u32_at(152)?,
+        erase_time_out: u32_at(156)?,
+        sectors,
+    })
+}
@@ -0,0 +1,203 @@
+//! Build the flash algorithm and generate probe-rs target descriptions.
+//!
+//! ```text
+//! cargo xtask build [PROFILE...]   build the algorithm and write target/<PROFILE>.yaml
+//! cargo xtask yaml PROFILE ELF     only generate the YAML for an already built ELF
+//! cargo xtask fcb PROFILE [OUT]    write a QuadSPI NOR FCB for the profile's flash
+//! cargo xtask checksum FILE        print the CRC32 and SHA-256 the target's checksum functions report for FILE
+//! cargo xtask list                 list the available profiles
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+mod elf;
+#[path = "../../src/fcb.rs"]
+mod fcb;
+#[path = "../../src/hash.rs"]
+mod hash;
+// Only built for their host tests.
+#[cfg(test)]
+#[path = "../../src/error.rs"]
+mod error;
+#[cfg(test)]
+#[path = "../../src/geometry.rs"]
+mod geometry;
+#[cfg(test)]
+#[path = "../../src/log/format.rs"]
+mod log_format;
+#[cfg(test)]
+#[path = "../../src/page.rs"]
+mod page;
+mod profile;
+#[path = "../../src/sector_map.rs"]
+mod sector_map;
+#[cfg(test)]
+#[path = "../../src/session.rs"]
+mod session;
+mod yaml;
+
+use profile::{Profile, PROFILES};
+
+const TARGET: &str = "thumbv7em-none-eabihf";
+const BINARY: &str = "imxrt-flash-algorithm";
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["build", names @ ..] => {
+            let profiles = if names.is_empty() {
+                PROFILES.iter().collect()
+            } else {
+                names
+                    .iter()
+                    .map(|name| find(name))
+                    .collect::<Result<Vec<_>>>()?
+            };
+            for profile in profiles {
+                build(profile)?;
+            }
+        }
+        ["yaml", name, elf] => {
+            let profile = find(name)?;
+            print!("{}", generate(profile, Path::new(elf))?);
+        }
+        ["fcb", name, out @ ..] if out.len() <= 1 => {
+            let profile = find(name)?;
+            let out = match out {
+                [out] => PathBuf::from(out),
+                _ => root()
+                    .join("target")
+                    .join(format!("{}-fcb.bin", profile.name)),
+            };
+            write_fcb(profile, &out)?;
+        }
+        ["checksum", file] => checksum(Path::new(file))?,
+        ["list"] => {
+            for profile in PROFILES {
+                println!("{}", profile.name);
+            }
+        }
+        _ => bail!(
+            "usage: xtask build [PROFILE...] | xtask yaml PROFILE ELF | xtask fcb PROFILE [OUT] | xtask checksum FILE | xtask list"
+        ),
+    }
+
+    Ok(())
+}
+
+fn find(name: &str) -> Result<&'static Profile> {
+    profile::find(name).ok_or_else(|| anyhow!("unknown profile `{name}`"))
+}
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+fn build(profile: &Profile) -> Result<()> {
+    let root = root();
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+
+    // Run from the repository root, so .cargo/config.toml picks the target
+    // and linker flags.
+    let status = Command::new(cargo)
+        .current_dir(&root)
+        .args(["build", "--release", "--no-default-features"])
+        .args(["--features", &profile.features.join(",")])
+        .envs(profile.env.iter().copied())
+        .status()
+        .context("failed to run cargo")?;
+    if !status.success() {
+        bail!("building the algorithm for {} failed", profile.name);
+    }
+
+    let elf = root
+        .join("target")
+        .join(TARGET)
+        .join("release")
+        .join(BINARY);
+    let yaml = generate(profile, &elf)?;
+
+    let out = root.join("target").join(format!("{}.yaml", profile.name));
+    std::fs::write(&out, yaml).with_context(|| format!("failed to write {}", out.display()))?;
+    println!("wrote {}", out.display());
+
+    Ok(())
+}
+
+fn generate(profile: &Profile, elf: &Path) -> Result<String> {
+    let data = std::fs::read(elf).with_context(|| format!("failed to read {}", elf.display()))?;
+    let algorithm = elf::read(&data)?;
+    if let Some(size) = profile.boot_flash_size() {
+        if size != algorithm.flash.size {
+            bail!(
+                "{} has a {:#x} byte boot flash, but the algorithm was built for {:#x} bytes (IMXRT_FLASH_SIZE)",
+                profile.name,
+                size,
+                algorithm.flash.size
+            );
+        }
+    }
+    let sectors = profile.sectors(algorithm.flash.size)?;
+    if sectors != algorithm.flash.sectors {
+        bail!(
+            "{} has the sectors {:x?}, but the algorithm was built with {:x?} (IMXRT_SECTOR_MAP)",
+            profile.name,
+            sectors,
+            algorithm.flash.sectors
+        );
+    }
+    Ok(yaml::render(profile, &algorithm))
+}
+
+/// Write the FCB the algorithm's default probe corresponds to, sized for the
+/// profile's boot flash, for firmware builds to place at its start.
+fn write_fcb(profile: &Profile, out: &Path) -> Result<()> {
+    let flash_size = profile
+        .boot_flash_size()
+        .ok_or_else(|| anyhow!("{} has no boot flash", profile.name))?;
+    let fcb = fcb::Builder::quad_spi_nor(flash_size)
+        .build()
+        .map_err(|err| {
+            anyhow!(
+                "invalid FCB: {} at offset {:#x} = {:#x}",
+                err.field.name(),
+                err.field.offset(),
+                err.value
+            )
+        })?;
+
+    std::fs::write(out, fcb.to_bytes())
+        .with_context(|| format!("failed to write {}", out.display()))?;
+    println!("wrote {}", out.display());
+
+    Ok(())
+}
+
+/// Print what the target's `Crc32` and `Sha256` functions leave in
+/// `CHECKSUM_RESULT` for flash holding `file`.
+fn checksum(file: &Path) -> Result<()> {
+    let data = std::fs::read(file).with_context(|| format!("failed to read {}", file.display()))?;
+
+    let mut crc = hash::Crc32::new();
+    crc.update(&data);
+    println!("crc32  {:08x}", crc.finish());
+
+    let mut sha = hash::Sha256::new();
+    sha.update(&data);
+    let digest: String = sha
+        .finish()
+        .iter()
+        .map(|word| format!("{word:08x}"))
+        .collect();
+    println!("sha256 {digest}");
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! Chip / board profiles the flash algorithm is built for.
+//!
+//! Everything that ends up in the target description and can't be read back
+//! from the algorithm's ELF lives here.
+
+use anyhow::{anyhow, Result};
+
+use crate::sector_map;
+
+pub struct Profile {
+    /// Name of the chip family and of the generated `<name>.yaml`.
+    pub name: &'static str,
+    /// Human readable family name.
+    pub family: &'static str,
+    /// JEP106 manufacturer code.
+    pub manufacturer: Jep106,
+    /// Cargo features the algorithm is built with for this profile.
+    pub features: &'static [&'static str],
+    /// Environment for the algorithm's build, e.g. the `IMXRT_LOG_*` log
+    /// console settings (see `build.rs`).
+    pub env: &'static [(&'static str, &'static str)],
+    pub variants: &'static [Variant],
+    /// Where probe-rs loads the algorithm.
+    pub load_address: u32,
+    pub stack_size: u32,
+}
+
+pub struct Jep106 {
+    pub id: u8,
+    pub cc: u8,
+}
+
+pub struct Variant {
+    pub name: &'static str,
+    pub memory_map: &'static [Memory],
+}
+
+pub struct Memory {
+    pub kind: MemoryKind,
+    pub name: &'static str,
+    pub start: u32,
+    pub end: u32,
+}
+
+pub enum MemoryKind {
+    Ram,
+    /// The flash the algorithm programs.
+    Nvm {
+        is_boot_memory: bool,
+    },
+}
+
+impl Profile {
+    /// The algorithm uses probe-rs' `miniz` transfer encoding.
+    pub fn miniz(&self) -> bool {
+        self.features.contains(&"miniz")
+    }
+
+    /// The value the profile sets for an environment variable of the
+    /// algorithm's build.
+    pub fn env(&self, var: &str) -> Option<&'static str> {
+        self.env
+            .iter()
+            .find(|(name, _)| *name == var)
+            .map(|(_, value)| *value)
+    }
+
+    /// `(size, address)` of the sector regions the profile's
+    /// `IMXRT_SECTOR_MAP` asks for, as the algorithm's `FlashDevice` lists
+    /// them for a `flash_size` byte flash.
+    pub fn sectors(&self, flash_size: u32) -> Result<Vec<(u32, u32)>> {
+        let map = self.env("IMXRT_SECTOR_MAP").unwrap_or(sector_map::DEFAULT);
+        let regions = sector_map::parse(map)
+            .map_err(|reason| anyhow!("{}: IMXRT_SECTOR_MAP={map}: {reason}", self.name))?;
+        let chips = match self.env("IMXRT_FLASH_CONNECTION") {
+            Some("parallel") => 2,
+            _ => 1,
+        };
+        Ok(sector_map::resolve(&regions, flash_size, chips))
+    }
+
+    /// Size of the flash the first variant boots from.
+    pub fn boot_flash_size(&self) -> Option<u32> {
+        self.variants
+            .first()?
+            .memory_map
+            .iter()
+            .find_map(|memory| match memory.kind {
+                MemoryKind::Nvm {
+                    is_boot_memory: true,
+                } => Some(memory.end - memory.start),
+                _ => None,
+            })
+    }
+}
+
+pub const PROFILES: &[Profile] = &[
+    Profile {
+        name: "MIMXRT1060",
+        family: "NXP MIMXRT1060 Series",
+        manufacturer: Jep106 { id: 21, cc: 0 },
+        features: &["miniz"],
+        env: &[],
+        variants: &[Variant {
+            name: "MIMXRT1060",
+            memory_map: &[
+                Memory {
+                    kind: MemoryKind::Nvm {
+                        is_boot_memory: true,
+                    },
+                    name: "FlexSPI1",
+                    start: 0x6000_0000,
+                    end: 0x6080_0000,
+                },
+                Memory {
+                    kind: MemoryKind::Ram,
+                    name: "ITCM",
+                    start: 0x0000_0000,
+                    end: 0x0002_0000,
+                },
+                Memory {
+                    kind: MemoryKind::Ram,
+                    name: "OCRAM2",
+                    start: 0x2020_0000,
+                    end: 0x202c_0000,
+                },
+            ],
+        }],
+        load_address: 0x2020_0020,
+        stack_size: 2048,
+    },
+    Profile {
+        name: "MIMXRT1060-S25FL256S",
+        family: "NXP MIMXRT1060 Series (S25FL256S)",
+        manufacturer: Jep106 { id: 21, cc: 0 },
+        features: &["miniz"],
+        // 32 MB, with 4 KB parameter sectors in the first 128 KB (the
+        // factory default sector architecture)
+        env: &[
+            ("IMXRT_FLASH_SIZE", "32M"),
+            ("IMXRT_SECTOR_MAP", "4K@0,64K@0x20000"),
+        ],
+        variants: &[Variant {
+            name: "MIMXRT1060_S25FL256S",
+            memory_map: &[
+                Memory {
+                    kind: MemoryKind::Nvm {
+                        is_boot_memory: true,
+                    },
+                    name: "FlexSPI1",
+                    start: 0x6000_0000,
+                    end: 0x6200_0000,
+                },
+                Memory {
+                    kind: MemoryKind::Ram,
+                    name: "ITCM",
+                    start: 0x0000_0000,
+                    end: 0x0002_0000,
+                },
+                Memory {
+                    kind: MemoryKind::Ram,
+                    name: "OCRAM2",
+                    start: 0x2020_0000,
+                    end: 0x202c_0000,
+                },
+            ],
+        }],
+        load_address: 0x2020_0020,
+        stack_size: 2048,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Profile> {
+    PROFILES
+        .iter()
+        .find(|profile| profile.name.eq_ignore_ascii_case(name))
+}
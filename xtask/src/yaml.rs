@@ -0,0 +1,147 @@
+//! Render a probe-rs target description.
+//!
+//! The output only depends on the profile and the extracted algorithm, so it
+//! can be compared against a checked-in snapshot.
+
+use std::fmt::Write;
+
+use base64::Engine;
+
+use crate::elf::FlashAlgorithm;
+use crate::profile::{MemoryKind, Profile};
+
+pub fn render(profile: &Profile, algorithm: &FlashAlgorithm) -> String {
+    let mut out = String::new();
+    // Writing to a String can't fail.
+    write_target(&mut out, profile, algorithm).unwrap();
+    out
+}
+
+fn write_target(
+    out: &mut String,
+    profile: &Profile,
+    algorithm: &FlashAlgorithm,
+) -> std::fmt::Result {
+    let algorithm_name = algorithm_name(profile);
+    let flash = &algorithm.flash;
+
+    writeln!(out, "name: {}", profile.family)?;
+    writeln!(out, "manufacturer:")?;
+    writeln!(out, "  id: {}", profile.manufacturer.id)?;
+    writeln!(out, "  cc: {}", profile.manufacturer.cc)?;
+    writeln!(out, "variants:")?;
+    for variant in profile.variants {
+        writeln!(out, "- name: {}", variant.name)?;
+        writeln!(out, "  cores:")?;
+        writeln!(out, "  - name: main")?;
+        writeln!(out, "    type: armv7em")?;
+        writeln!(out, "    core_access_options: !Arm")?;
+        writeln!(out, "      ap: 0")?;
+        writeln!(out, "      psel: 0")?;
+        writeln!(out, "  memory_map:")?;
+        for memory in variant.memory_map {
+            match memory.kind {
+                MemoryKind::Nvm { .. } => writeln!(out, "  - !Nvm")?,
+                MemoryKind::Ram => writeln!(out, "  - !Ram")?,
+            }
+            writeln!(out, "    name: {}", memory.name)?;
+            writeln!(out, "    range:")?;
+            writeln!(out, "      start: {:#x}", memory.start)?;
+            writeln!(out, "      end: {:#x}", memory.end)?;
+            if let MemoryKind::Nvm { is_boot_memory } = memory.kind {
+                writeln!(out, "    is_boot_memory: {is_boot_memory}")?;
+            }
+            writeln!(out, "    cores:")?;
+            writeln!(out, "    - main")?;
+        }
+        writeln!(out, "  flash_algorithms:")?;
+        writeln!(out, "  - {algorithm_name}")?;
+    }
+
+    writeln!(out, "flash_algorithms:")?;
+    writeln!(out, "- name: {algorithm_name}")?;
+    writeln!(out, "  description: {}", flash.name)?;
+    writeln!(out, "  default: true")?;
+    writeln!(
+        out,
+        "  instructions: {}",
+        base64::engine::general_purpose::STANDARD.encode(&algorithm.instructions)
+    )?;
+    writeln!(out, "  load_address: {:#x}", profile.load_address)?;
+    writeln!(out, "  pc_init: {:#x}", algorithm.pc_init)?;
+    writeln!(out, "  pc_uninit: {:#x}", algorithm.pc_uninit)?;
+    writeln!(out, "  pc_program_page: {:#x}", algorithm.pc_program_page)?;
+    writeln!(out, "  pc_erase_sector: {:#x}", algorithm.pc_erase_sector)?;
+    if let Some(pc_erase_all) = algorithm.pc_erase_all {
+        writeln!(out, "  pc_erase_all: {pc_erase_all:#x}")?;
+    }
+    if let Some(pc_verify) = algorithm.pc_verify {
+        writeln!(out, "  pc_verify: {pc_verify:#x}")?;
+    }
+    writeln!(
+        out,
+        "  data_section_offset: {:#x}",
+        algorithm.data_section_offset
+    )?;
+    if let Some(rtt_location) = algorithm.rtt_location {
+        writeln!(out, "  rtt_location: {rtt_location:#x}")?;
+    }
+    writeln!(out, "  stack_size: {}", profile.stack_size)?;
+    if profile.miniz() {
+        writeln!(out, "  transfer_encoding: miniz")?;
+    }
+    writeln!(out, "  flash_properties:")?;
+    writeln!(out, "    address_range:")?;
+    writeln!(out, "      start: {:#x}", flash.address)?;
+    writeln!(out, "      end: {:#x}", flash.address + flash.size)?;
+    writeln!(out, "    page_size: {:#x}", flash.page_size)?;
+    writeln!(out, "    erased_byte_value: {:#x}", flash.empty)?;
+    writeln!(out, "    program_page_timeout: {}", flash.program_time_out)?;
+    writeln!(out, "    erase_sector_timeout: {}", flash.erase_time_out)?;
+    writeln!(out, "    sectors:")?;
+    for (size, address) in &flash.sectors {
+        writeln!(out, "    - size: {size:#x}")?;
+        writeln!(out, "      address: {address:#x}")?;
+    }
+    writeln!(out, "  cores:")?;
+    writeln!(out, "  - main")?;
+
+    Ok(())
+}
+
+fn algorithm_name(profile: &Profile) -> String {
+    format!("{}-flexspi", profile.name.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::profile::PROFILES;
+
+    /// Render `fixtures/<profile>.elf` and compare against
+    /// `snapshots/<profile>.yaml`. The fixtures are assembled from
+    /// `fixtures/algorithm.s`, with the flash description the profile's build
+    /// produces. Set `UPDATE_SNAPSHOTS` to write the snapshots instead, then
+    /// review the diff.
+    #[test]
+    fn snapshots() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        for profile in PROFILES {
+            let elf = dir.join("fixtures").join(format!("{}.elf", profile.name));
+            let yaml = crate::generate(profile, &elf).unwrap();
+            let path = dir.join("snapshots").join(format!("{}.yaml", profile.name));
+            if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+                std::fs::write(&path, &yaml).unwrap();
+                continue;
+            }
+            let snapshot = std::fs::read_to_string(&path)
+                .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+            assert!(
+                yaml == snapshot,
+                "{} doesn't match the rendered YAML:\n{yaml}",
+                path.display()
+            );
+        }
+    }
+}